@@ -14,8 +14,8 @@
 extern crate libuv;
 use libuv::prelude::*;
 use libuv::{
-    exepath, ProcessHandle, ProcessOptions, StdioContainer, StdioFlags, StdioType, TcpBindFlags,
-    TcpHandle,
+    exepath, ProcessHandle, ProcessOptions, Signal, StdioContainer, StdioFlags, StdioType,
+    TcpBindFlags, TcpHandle,
 };
 use std::net::Ipv4Addr;
 use std::path::PathBuf;
@@ -43,15 +43,16 @@ fn invoke_cgi_script(client: TcpHandle) -> Result<(), Box<dyn std::error::Error>
     options.stdio = &child_stdio;
 
     let mut client_clone = client.clone();
-    options.exit_cb = (move |mut handle: ProcessHandle, exit_status: i64, term_signal: i32| {
-        println!(
-            "Process exited with status {}, signal {}",
-            exit_status, term_signal
-        );
-        handle.close(());
-        client_clone.close(());
-    })
-    .into();
+    options.exit_cb =
+        (move |mut handle: ProcessHandle, exit_status: i64, term_signal: Option<Signal>| {
+            match term_signal {
+                Some(signal) => println!("Process terminated by signal {}", signal),
+                None => println!("Process exited with status {}", exit_status),
+            }
+            handle.close(());
+            client_clone.close(());
+        })
+        .into();
 
     client.get_loop().spawn_process(options)?;
 
@@ -22,8 +22,8 @@
 extern crate libuv;
 use libuv::prelude::*;
 use libuv::{
-    cpu_info, exepath, Buf, PipeHandle, ProcessHandle, ProcessOptions, StdioContainer, StdioFlags,
-    StdioType, TcpBindFlags,
+    cpu_info, exepath, Buf, PipeHandle, ProcessHandle, ProcessOptions, Signal, StdioContainer,
+    StdioFlags, StdioType, TcpBindFlags,
 };
 use std::net::Ipv4Addr;
 use std::path::PathBuf;
@@ -53,11 +53,11 @@ impl Workers {
     }
 }
 
-fn close_process_handle(mut handle: ProcessHandle, exit_status: i64, term_signal: i32) {
-    println!(
-        "Process exited with status {}, signal {}",
-        exit_status, term_signal
-    );
+fn close_process_handle(mut handle: ProcessHandle, exit_status: i64, term_signal: Option<Signal>) {
+    match term_signal {
+        Some(signal) => println!("Process terminated by signal {}", signal),
+        None => println!("Process exited with status {}", exit_status),
+    }
     handle.close(());
 }
 
@@ -12,14 +12,16 @@
 
 extern crate libuv;
 use libuv::prelude::*;
-use libuv::{exepath, ProcessHandle, ProcessOptions, StdioContainer, StdioFlags, StdioType};
+use libuv::{
+    exepath, ProcessHandle, ProcessOptions, Signal, StdioContainer, StdioFlags, StdioType,
+};
 use std::path::PathBuf;
 
-fn on_exit(mut handle: ProcessHandle, exit_status: i64, term_signal: i32) {
-    println!(
-        "Process exited with status {}, signal {}",
-        exit_status, term_signal
-    );
+fn on_exit(mut handle: ProcessHandle, exit_status: i64, term_signal: Option<Signal>) {
+    match term_signal {
+        Some(signal) => println!("Process terminated by signal {}", signal),
+        None => println!("Process exited with status {}", exit_status),
+    }
     handle.close(());
 }
 
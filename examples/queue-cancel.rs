@@ -8,8 +8,7 @@
 
 extern crate libuv;
 use libuv::prelude::*;
-use libuv::{SignalHandle, WorkReq};
-use libuv_sys2::SIGINT;
+use libuv::{Signal, SignalHandle, WorkReq};
 
 const FIB_UNTIL: usize = 25;
 
@@ -62,7 +61,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut sig = r#loop.signal()?;
     sig.start(
         move |handle, _| signal_handler(handle, &mut reqs),
-        SIGINT as _,
+        Signal::SIGINT,
     )?;
 
     r#loop.run(RunMode::Default)?;
@@ -6,13 +6,13 @@
 
 extern crate libuv;
 use libuv::prelude::*;
-use libuv::{ProcessHandle, ProcessOptions};
+use libuv::{ProcessHandle, ProcessOptions, Signal};
 
-fn on_exit(mut handle: ProcessHandle, exit_status: i64, term_signal: i32) {
-    println!(
-        "Process exited with status {}, signal {}",
-        exit_status, term_signal
-    );
+fn on_exit(mut handle: ProcessHandle, exit_status: i64, term_signal: Option<Signal>) {
+    match term_signal {
+        Some(signal) => println!("Process terminated by signal {}", signal),
+        None => println!("Process exited with status {}", exit_status),
+    }
     handle.close(());
 }
 
@@ -164,10 +164,16 @@ where
         panic!("Buf index starts at {} but ends at {}", start, end);
     }
 
-    if len <= end {
+    if len < end {
         panic!("index {} out of range for Buf of length {}", end, len);
     }
 
+    // an unallocated Buf has a null base, which from_raw_parts() does not accept even for an empty
+    // slice
+    if !buf.is_allocated() {
+        return &[];
+    }
+
     unsafe { std::slice::from_raw_parts((*buf.buf).base.add(start) as *const u8, end - start) }
 }
 
@@ -255,6 +261,21 @@ impl Buf {
         Ok(Box::into_raw(buf).into_inner())
     }
 
+    /// Create a new Buf holding a copy of the given bytes, without a null terminator. Returns
+    /// EINVAL if bytes is empty.
+    pub(crate) fn from_slice(bytes: &[u8]) -> crate::Result<Buf> {
+        if bytes.is_empty() {
+            return Err(crate::Error::EINVAL);
+        }
+        let buf = Buf::with_capacity(bytes.len())?;
+        unsafe {
+            (*buf.buf)
+                .base
+                .copy_from_nonoverlapping(bytes.as_ptr() as _, bytes.len())
+        };
+        Ok(buf)
+    }
+
     /// Create a Buf with the given capacity - the memory is not initialized
     pub fn with_capacity(size: usize) -> crate::Result<Buf> {
         let base = Buf::alloc(size)?;
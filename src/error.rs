@@ -24,6 +24,12 @@ impl Error {
     }
 }
 
+impl crate::IntoInner<uv::uv_errno_t> for Error {
+    fn into_inner(self) -> uv::uv_errno_t {
+        self.code()
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}: {}", self.name(), self.message())
@@ -51,3 +57,35 @@ impl Display for ConversionError {
 }
 
 impl std::error::Error for ConversionError {}
+
+/// Returned by Loop::shutdown_gracefully() when some handles were still open after the deadline
+/// expired. These handles have been forcibly closed.
+#[derive(Clone, Debug)]
+pub struct ShutdownError {
+    handles: Vec<crate::HandleType>,
+}
+
+impl ShutdownError {
+    pub(crate) fn new(handles: Vec<crate::HandleType>) -> ShutdownError {
+        ShutdownError { handles }
+    }
+
+    /// The types of the handles that did not close before the deadline.
+    pub fn handles(&self) -> &[crate::HandleType] {
+        &self.handles
+    }
+}
+
+impl Display for ShutdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<String> = self.handles.iter().map(|t| t.name()).collect();
+        write!(
+            f,
+            "{} handle(s) did not close before the deadline: {}",
+            self.handles.len(),
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ShutdownError {}
@@ -17,7 +17,9 @@ include!("./fs_mode_flags.rs");
 include!("./fs_symlink_flags.inc.rs");
 include!("./fs_types.inc.rs");
 
-use crate::{FromInner, FsReq, Inner, IntoInner};
+#[cfg(feature = "testing")]
+use crate::testing::fs_faults::FsTarget;
+use crate::{FromInner, FsReq, Inner, IntoInner, ToReq};
 use std::ffi::CString;
 use uv::{
     uv_fs_access, uv_fs_chmod, uv_fs_chown, uv_fs_close, uv_fs_closedir, uv_fs_copyfile,
@@ -78,6 +80,22 @@ pub type Gid = u8;
 #[cfg(not(windows))]
 pub type Gid = u32;
 
+/// Creates the FsReq for an operation. With the testing feature enabled, the fault rules for the
+/// operation are applied first - if one of them fails the operation, the calling function returns
+/// the request (async) or the error (sync) without starting the operation.
+macro_rules! fs_req {
+    ($loop:expr, $cb:expr, $uv_cb:expr, $fs_type:expr, $target:expr) => {{
+        #[allow(unused_mut)]
+        let mut req = FsReq::new($cb)?;
+        req.to_req().set_loop($loop);
+        #[cfg(feature = "testing")]
+        if $loop.fs_fault_start(&mut req, $fs_type, $target, $uv_cb.is_some())? {
+            return Ok(req);
+        }
+        req
+    }};
+}
+
 /// Destroys the given FsReq and returns the result
 fn destroy_req_return_result(mut req: FsReq) -> SyncResult {
     let result = req.result();
@@ -95,7 +113,7 @@ impl crate::Loop {
     fn _fs_close<CB: Into<crate::FsCB<'static>>>(&self, file: File, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::CLOSE, FsTarget::File(file));
         let result =
             crate::uvret(unsafe { uv_fs_close(self.into_inner(), req.inner(), file as _, uv_cb) });
         if result.is_err() {
@@ -125,7 +143,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::OPEN, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_open(
                 self.into_inner(),
@@ -140,6 +158,11 @@ impl crate::Loop {
         if result.is_err() {
             req.destroy();
         }
+
+        #[cfg(feature = "testing")]
+        if result.is_ok() && uv_cb.is_none() {
+            crate::testing::fs_faults::record_open(self, &req);
+        }
         result.map(|_| req)
     }
 
@@ -182,8 +205,10 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::READ, FsTarget::File(file));
         let (bufs_ptr, bufs_len, _) = bufs.into_inner();
+        #[cfg(feature = "testing")]
+        self.fs_fault_short_count(FsType::READ, file, bufs_ptr, bufs_len);
         let result = crate::uvret(unsafe {
             uv_fs_read(
                 self.into_inner(),
@@ -231,7 +256,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::UNLINK, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_unlink(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -263,8 +288,10 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::WRITE, FsTarget::File(file));
         let (bufs_ptr, bufs_len, _) = bufs.into_inner();
+        #[cfg(feature = "testing")]
+        self.fs_fault_short_count(FsType::WRITE, file, bufs_ptr, bufs_len);
         let result = crate::uvret(unsafe {
             uv_fs_write(
                 self.into_inner(),
@@ -322,7 +349,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::MKDIR, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_mkdir(
                 self.into_inner(),
@@ -364,7 +391,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let tpl = CString::new(tpl)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::MKDTEMP, FsTarget::Path(&tpl));
         let result = crate::uvret(unsafe {
             uv_fs_mkdtemp(self.into_inner(), req.inner(), tpl.as_ptr(), uv_cb)
         })
@@ -394,7 +421,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let tpl = CString::new(tpl)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::MKSTEMP, FsTarget::Path(&tpl));
         let result = crate::uvret(unsafe {
             uv_fs_mkstemp(self.into_inner(), req.inner(), tpl.as_ptr(), uv_cb)
         })
@@ -421,7 +448,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::RMDIR, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_rmdir(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -448,7 +475,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::OPENDIR, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_opendir(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -487,7 +514,7 @@ impl crate::Loop {
     fn _fs_closedir<CB: Into<crate::FsCB<'static>>>(&self, dir: &Dir, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::CLOSEDIR, FsTarget::None);
         let result = crate::uvret(unsafe {
             uv_fs_closedir(self.into_inner(), req.inner(), dir.into_inner(), uv_cb)
         });
@@ -514,7 +541,7 @@ impl crate::Loop {
     fn _fs_readdir<CB: Into<crate::FsCB<'static>>>(&self, dir: &Dir, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::READDIR, FsTarget::None);
         let result = crate::uvret(unsafe {
             uv_fs_readdir(self.into_inner(), req.inner(), dir.into_inner(), uv_cb)
         });
@@ -569,7 +596,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::SCANDIR, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_scandir(
                 self.into_inner(),
@@ -618,7 +645,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::STAT, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_stat(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -647,7 +674,7 @@ impl crate::Loop {
     fn _fs_fstat<CB: Into<crate::FsCB<'static>>>(&self, file: File, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FSTAT, FsTarget::File(file));
         let result =
             crate::uvret(unsafe { uv_fs_fstat(self.into_inner(), req.inner(), file as _, uv_cb) });
         if result.is_err() {
@@ -675,7 +702,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::LSTAT, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_lstat(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -705,7 +732,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::STATFS, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_statfs(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -747,7 +774,7 @@ impl crate::Loop {
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
         let new_path = CString::new(new_path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::RENAME, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_rename(
                 self.into_inner(),
@@ -784,7 +811,7 @@ impl crate::Loop {
     fn _fs_fsync<CB: Into<crate::FsCB<'static>>>(&self, file: File, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FSYNC, FsTarget::File(file));
         let result =
             crate::uvret(unsafe { uv_fs_fsync(self.into_inner(), req.inner(), file as _, uv_cb) });
         if result.is_err() {
@@ -813,7 +840,7 @@ impl crate::Loop {
     fn _fs_fdatasync<CB: Into<crate::FsCB<'static>>>(&self, file: File, cb: CB) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FDATASYNC, FsTarget::File(file));
         let result = crate::uvret(unsafe {
             uv_fs_fdatasync(self.into_inner(), req.inner(), file as _, uv_cb)
         });
@@ -843,7 +870,7 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FTRUNCATE, FsTarget::File(file));
         let result = crate::uvret(unsafe {
             uv_fs_ftruncate(self.into_inner(), req.inner(), file as _, offset, uv_cb)
         });
@@ -881,7 +908,7 @@ impl crate::Loop {
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
         let new_path = CString::new(new_path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::COPYFILE, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_copyfile(
                 self.into_inner(),
@@ -960,7 +987,7 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::SENDFILE, FsTarget::File(in_file));
         let result = crate::uvret(unsafe {
             uv_fs_sendfile(
                 self.into_inner(),
@@ -1012,7 +1039,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::ACCESS, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_access(
                 self.into_inner(),
@@ -1055,7 +1082,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::CHMOD, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_chmod(
                 self.into_inner(),
@@ -1097,7 +1124,7 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FCHMOD, FsTarget::File(file));
         let result = crate::uvret(unsafe {
             uv_fs_fchmod(
                 self.into_inner(),
@@ -1139,7 +1166,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::UTIME, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_utime(
                 self.into_inner(),
@@ -1190,7 +1217,7 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FUTIME, FsTarget::File(file));
         let result = crate::uvret(unsafe {
             uv_fs_futime(
                 self.into_inner(),
@@ -1241,7 +1268,7 @@ impl crate::Loop {
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
         let new_path = CString::new(new_path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::LINK, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_link(
                 self.into_inner(),
@@ -1286,7 +1313,7 @@ impl crate::Loop {
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
         let new_path = CString::new(new_path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::SYMLINK, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_symlink(
                 self.into_inner(),
@@ -1342,7 +1369,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::READLINK, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_readlink(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -1376,7 +1403,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::REALPATH, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_realpath(self.into_inner(), req.inner(), path.as_ptr(), uv_cb)
         })
@@ -1455,7 +1482,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::CHOWN, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_chown(
                 self.into_inner(),
@@ -1504,7 +1531,7 @@ impl crate::Loop {
     ) -> FsReqResult {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::FCHOWN, FsTarget::File(file));
         let result = crate::uvret(unsafe {
             uv_fs_fchown(
                 self.into_inner(),
@@ -1553,7 +1580,7 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(crate::uv_fs_cb, cb);
         let path = CString::new(path)?;
-        let mut req = FsReq::new(cb)?;
+        let mut req = fs_req!(self, cb, uv_cb, FsType::LCHOWN, FsTarget::Path(&path));
         let result = crate::uvret(unsafe {
             uv_fs_lchown(
                 self.into_inner(),
@@ -8,7 +8,7 @@ use uv::{
 };
 
 callbacks! {
-    pub ExitCB(handle: ProcessHandle, exit_status: i64, term_signal: i32);
+    pub ExitCB(handle: ProcessHandle, exit_status: i64, term_signal: Option<crate::Signal>);
 }
 
 /// Additional data stored on the handle
@@ -27,8 +27,14 @@ extern "C" fn uv_exit_cb(
     if !dataptr.is_null() {
         unsafe {
             if let super::ProcessData(d) = &mut (*dataptr).addl {
+                // libuv reports a term_signal of 0 if the process was not killed by a signal
+                let term_signal = if term_signal != 0 {
+                    Some(crate::Signal::from_raw(term_signal as _))
+                } else {
+                    None
+                };
                 d.exit_cb
-                    .call(handle.into_inner(), exit_status, term_signal as _);
+                    .call(handle.into_inner(), exit_status, term_signal);
             }
         }
     }
@@ -349,14 +355,14 @@ impl ProcessHandle {
 
     /// Sends the specified signal to the given process handle. Check the documentation on
     /// SignalHandle for signal support, specially on Windows.
-    pub fn kill(&mut self, signum: i32) -> crate::Result<()> {
-        crate::uvret(unsafe { uv_process_kill(self.handle, signum) })
+    pub fn kill<S: Into<crate::Signal>>(&mut self, signal: S) -> crate::Result<()> {
+        crate::uvret(unsafe { uv_process_kill(self.handle, signal.into().as_raw()) })
     }
 
     /// Sends the specified signal to the given PID. Check the documentation on SignalHandle for
     /// signal support, specially on Windows.
-    pub fn kill_pid(pid: i32, signum: i32) -> crate::Result<()> {
-        crate::uvret(unsafe { uv_kill(pid, signum) })
+    pub fn kill_pid<S: Into<crate::Signal>>(pid: i32, signal: S) -> crate::Result<()> {
+        crate::uvret(unsafe { uv_kill(pid, signal.into().as_raw()) })
     }
 }
 
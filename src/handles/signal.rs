@@ -3,7 +3,7 @@ use std::convert::TryFrom;
 use uv::{uv_signal_init, uv_signal_start, uv_signal_start_oneshot, uv_signal_stop, uv_signal_t};
 
 callbacks! {
-    pub SignalCB(handle: SignalHandle, signum: i32);
+    pub SignalCB(handle: SignalHandle, signal: crate::Signal);
 }
 
 /// Additional data stored on the handle
@@ -16,10 +16,15 @@ pub(crate) struct SignalDataFields<'a> {
 extern "C" fn uv_signal_cb(handle: *mut uv_signal_t, signum: std::os::raw::c_int) {
     let dataptr = crate::Handle::get_data(uv_handle!(handle));
     if !dataptr.is_null() {
-        unsafe {
-            if let super::SignalData(d) = &mut (*dataptr).addl {
-                d.signal_cb.call(handle.into_inner(), signum as _);
-            }
+        // The callback is taken out of the data while it runs: it may restart the handle with a new
+        // callback, which would otherwise drop the closure that is running.
+        let mut cb = match unsafe { &mut (*dataptr).addl } {
+            super::SignalData(d) => std::mem::take(&mut d.signal_cb),
+            _ => return,
+        };
+        cb.call(handle.into_inner(), crate::Signal::from_raw(signum as _));
+        if let super::SignalData(d) = unsafe { &mut (*dataptr).addl } {
+            d.signal_cb.restore(cb);
         }
     }
 }
@@ -77,7 +82,11 @@ impl SignalHandle {
     }
 
     /// Start the handle with the given callback, watching for the given signal.
-    pub fn start<CB: Into<SignalCB<'static>>>(&mut self, cb: CB, signum: i32) -> crate::Result<()> {
+    pub fn start<CB: Into<SignalCB<'static>>, S: Into<crate::Signal>>(
+        &mut self,
+        cb: CB,
+        signal: S,
+    ) -> crate::Result<()> {
         // uv_cb is either Some(uv_signal_cb) or None
         let cb = cb.into();
         let uv_cb = use_c_callback!(uv_signal_cb, cb);
@@ -90,15 +99,16 @@ impl SignalHandle {
             }
         }
 
+        let signum = signal.into().as_raw();
         crate::uvret(unsafe { uv_signal_start(self.handle, uv_cb, signum as _) })
     }
 
     /// Same functionality as start() but the signal handler is reset the moment the signal is
     /// received.
-    pub fn start_oneshot<CB: Into<SignalCB<'static>>>(
+    pub fn start_oneshot<CB: Into<SignalCB<'static>>, S: Into<crate::Signal>>(
         &mut self,
         cb: CB,
-        signum: i32,
+        signal: S,
     ) -> crate::Result<()> {
         // uv_cb is either Some(uv_signal_cb) or None
         let cb = cb.into();
@@ -112,6 +122,7 @@ impl SignalHandle {
             }
         }
 
+        let signum = signal.into().as_raw();
         crate::uvret(unsafe { uv_signal_start_oneshot(self.handle, uv_cb, signum as _) })
     }
 
@@ -121,8 +132,8 @@ impl SignalHandle {
     }
 
     /// Signal being monitored by this handle.
-    pub fn signum(&self) -> i32 {
-        unsafe { (*self.handle).signum }
+    pub fn signum(&self) -> crate::Signal {
+        crate::Signal::from_raw(unsafe { (*self.handle).signum } as _)
     }
 }
 
@@ -1,4 +1,6 @@
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum AddlStreamData<'a> {
     NoAddlStreamData,
+    TtyData(crate::TtyDataFields),
     UdpData(crate::UdpDataFields<'a>),
 }
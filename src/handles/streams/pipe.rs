@@ -1,4 +1,4 @@
-use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle};
+use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle, ToReq};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::CString;
 use std::net::SocketAddr;
@@ -106,6 +106,7 @@ impl PipeHandle {
         cb: CB,
     ) -> Result<crate::ConnectReq, Box<dyn std::error::Error>> {
         let req = crate::ConnectReq::new(cb)?;
+        req.to_req().set_loop(&self.get_loop());
         let name = CString::new(name)?;
         unsafe {
             uv_pipe_connect(
@@ -118,6 +119,29 @@ impl PipeHandle {
         Ok(req)
     }
 
+    /// Same as connect(), but gives up if the connection has not been established after timeout
+    /// milliseconds. In that case, the handle is closed and the callback receives ETIMEDOUT.
+    pub fn connect_timeout<CB: Into<crate::ConnectCB<'static>>>(
+        &mut self,
+        name: &str,
+        timeout: u64,
+        cb: CB,
+    ) -> Result<crate::ConnectReq, Box<dyn std::error::Error>> {
+        let deadline = crate::Deadline::start(self.to_handle(), timeout)?;
+        let connect_deadline = deadline.clone();
+        let mut cb = cb.into();
+        let result = self.connect(
+            name,
+            move |req: crate::ConnectReq, status: crate::Result<u32>| {
+                cb.call(req, connect_deadline.finish(status))
+            },
+        );
+        if result.is_err() {
+            deadline.disarm();
+        }
+        result
+    }
+
     /// Get the name of the Unix domain socket or the named pipe.
     pub fn getsockname(&self) -> Result<SocketAddr, Box<dyn std::error::Error>> {
         let mut sockaddr: uv::sockaddr_storage = unsafe { std::mem::zeroed() };
@@ -1,4 +1,4 @@
-use crate::{FromInner, Inner, IntoInner, NREAD};
+use crate::{FromInner, HandleTrait, Inner, IntoInner, ToReq, NREAD};
 use uv::{
     uv_accept, uv_is_readable, uv_is_writable, uv_listen, uv_read_start, uv_read_stop, uv_shutdown,
     uv_stream_get_write_queue_size, uv_stream_set_blocking, uv_stream_t, uv_try_write,
@@ -16,9 +16,24 @@ pub(crate) struct StreamDataFields<'a> {
     pub(crate) alloc_cb: AllocCB<'a>,
     connection_cb: ConnectionCB<'a>,
     read_cb: ReadCB<'a>,
+    read_timer: Option<ReadTimer>,
     pub(crate) addl: super::AddlStreamData<'a>,
 }
 
+/// The idle timer of a stream that is reading with read_start_timeout(). The timer is closed when
+/// reading is restarted without a timeout, or when the stream is closed.
+struct ReadTimer {
+    timer: crate::TimerHandle,
+}
+
+impl Drop for ReadTimer {
+    fn drop(&mut self) {
+        if !self.timer.is_closing() {
+            self.timer.close(());
+        }
+    }
+}
+
 /// Callback for uv_recv_start, uv_udp_recv_start
 pub(crate) extern "C" fn uv_alloc_cb(
     handle: *mut uv::uv_handle_t,
@@ -77,6 +92,51 @@ extern "C" fn uv_read_cb(stream: *mut uv_stream_t, nread: NREAD, buf: *const uv:
     }
 }
 
+/// Called when a stream that is reading with read_start_timeout() has not received any data for
+/// the timeout: the read callback receives ETIMEDOUT and the stream is closed.
+fn read_timed_out(mut stream: StreamHandle) {
+    let dataptr = StreamHandle::get_data(stream.handle);
+    if !dataptr.is_null() {
+        unsafe {
+            if let Some(read_timer) = &mut (*dataptr).read_timer {
+                let _ = read_timer.timer.stop();
+            }
+            let buf: uv::uv_buf_t = std::mem::zeroed();
+            (*dataptr).read_cb.call(
+                stream,
+                Err(crate::Error::ETIMEDOUT),
+                (&buf as *const uv::uv_buf_t).into_inner(),
+            );
+        }
+    }
+
+    if !stream.is_closing() {
+        stream.close(());
+    }
+}
+
+/// Write a copy of data to a stream, so that data can be dropped as soon as this returns. The copy
+/// is destroyed when the write finishes, before cb is called with its status. Returns EINVAL if
+/// data is empty.
+pub(crate) fn write_copy(
+    stream: &mut impl StreamTrait,
+    data: &[u8],
+    mut cb: impl FnMut(crate::Result<u32>) + 'static,
+) -> crate::Result<()> {
+    let buf = crate::Buf::from_slice(data)?;
+    let result = stream.write(&[buf], move |_, status: crate::Result<u32>| {
+        let mut buf = buf;
+        buf.destroy();
+        cb(status);
+    });
+    if let Err(e) = result {
+        let mut buf = buf;
+        buf.destroy();
+        return Err(e);
+    }
+    Ok(())
+}
+
 /// Stream handles provide an abstraction of a duplex communication channel. StreamHandle is an
 /// abstract type, libuv provides 3 stream implementations in the form of TcpHandle, PipeHandle and
 /// TtyHandle.
@@ -91,11 +151,33 @@ impl StreamHandle {
             alloc_cb: ().into(),
             connection_cb: ().into(),
             read_cb: ().into(),
+            read_timer: None,
             addl,
         });
         crate::Handle::initialize_data(uv_handle!(stream), data);
     }
 
+    /// Start reading again with the callbacks that were passed to the last read_start(). Used to
+    /// resume a stream that was paused with read_stop(). The read timeout, if any, restarts.
+    pub(crate) fn read_resume(&mut self) -> crate::Result<()> {
+        let dataptr = StreamHandle::get_data(self.handle);
+        if dataptr.is_null() {
+            return Err(crate::Error::EINVAL);
+        }
+
+        let (uv_alloc_cb, uv_read_cb) = unsafe {
+            (
+                use_c_callback!(uv_alloc_cb, (*dataptr).alloc_cb),
+                use_c_callback!(uv_read_cb, (*dataptr).read_cb),
+            )
+        };
+        crate::uvret(unsafe { uv_read_start(self.handle, uv_alloc_cb, uv_read_cb) })?;
+        if let Some(read_timer) = unsafe { &mut (*dataptr).read_timer } {
+            read_timer.timer.again()?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_data<'a>(stream: *mut uv_stream_t) -> *mut StreamDataFields<'a> {
         if let super::super::StreamData(ref mut d) =
             unsafe { &mut (*crate::Handle::get_data(uv_handle!(stream))).addl }
@@ -163,6 +245,7 @@ pub trait StreamTrait: ToStream {
         cb: CB,
     ) -> crate::Result<crate::ShutdownReq> {
         let mut req = crate::ShutdownReq::new(cb)?;
+        req.to_req().set_loop(&self.to_stream().get_loop());
         let result = crate::uvret(unsafe {
             uv_shutdown(
                 req.inner(),
@@ -233,12 +316,63 @@ pub trait StreamTrait: ToStream {
             unsafe {
                 (*dataptr).alloc_cb = alloc_cb;
                 (*dataptr).read_cb = read_cb;
+                (*dataptr).read_timer = None;
             }
         }
 
         crate::uvret(unsafe { uv_read_start(self.to_stream().inner(), uv_alloc_cb, uv_read_cb) })
     }
 
+    /// Same as read_start(), but with an idle timeout: if no data is read for timeout
+    /// milliseconds, the read_cb callback receives ETIMEDOUT and the stream is closed. The timeout
+    /// restarts every time data is received. read_stop() pauses the timeout.
+    fn read_start_timeout<ACB: Into<AllocCB<'static>>, RCB: Into<ReadCB<'static>>>(
+        &mut self,
+        alloc_cb: ACB,
+        read_cb: RCB,
+        timeout: u64,
+    ) -> crate::Result<()> {
+        let stream = self.to_stream();
+        let mut timer = crate::TimerHandle::new(&stream.get_loop())?;
+        let result = timer.start(timeout, timeout, move |_: crate::TimerHandle| {
+            read_timed_out(stream)
+        });
+        if let Err(e) = result {
+            timer.close(());
+            return Err(e);
+        }
+
+        let mut read_cb = read_cb.into();
+        let result = self.read_start(
+            alloc_cb,
+            move |stream: StreamHandle, nread: crate::Result<usize>, buf: crate::ReadonlyBuf| {
+                let dataptr = StreamHandle::get_data(stream.handle);
+                if !dataptr.is_null() {
+                    if let Some(read_timer) = unsafe { &mut (*dataptr).read_timer } {
+                        let _ = match nread {
+                            Ok(0) => Ok(()),
+                            Ok(_) => read_timer.timer.again(),
+                            Err(_) => read_timer.timer.stop(),
+                        };
+                    }
+                }
+                read_cb.call(stream, nread, buf);
+            },
+        );
+        if let Err(e) = result {
+            timer.close(());
+            return Err(e);
+        }
+
+        let dataptr = StreamHandle::get_data(stream.handle);
+        if dataptr.is_null() {
+            timer.close(());
+        } else {
+            unsafe { (*dataptr).read_timer = Some(ReadTimer { timer }) };
+        }
+        Ok(())
+    }
+
     /// Stop reading data from the stream. The uv_read_cb callback will no longer be called.
     ///
     /// This function is idempotent and may be safely called on a stopped stream.
@@ -247,6 +381,12 @@ pub trait StreamTrait: ToStream {
     /// non-zero return indicates that finishing releasing resources may be pending on the next
     /// input event on that TTY on Windows, and does not indicate failure.
     fn read_stop(&mut self) -> crate::Result<()> {
+        let dataptr = StreamHandle::get_data(self.to_stream().inner());
+        if !dataptr.is_null() {
+            if let Some(read_timer) = unsafe { &mut (*dataptr).read_timer } {
+                let _ = read_timer.timer.stop();
+            }
+        }
         crate::uvret(unsafe { uv_read_stop(self.to_stream().inner()) })
     }
 
@@ -260,6 +400,7 @@ pub trait StreamTrait: ToStream {
         cb: CB,
     ) -> crate::Result<crate::WriteReq> {
         let mut req = crate::WriteReq::new(bufs, cb)?;
+        req.to_req().set_loop(&self.to_stream().get_loop());
         let result = crate::uvret(unsafe {
             uv_write(
                 req.inner(),
@@ -275,6 +416,29 @@ pub trait StreamTrait: ToStream {
         result.map(|_| req)
     }
 
+    /// Same as write(), but gives up if the data has not been written after timeout milliseconds.
+    /// In that case, the stream is closed and the callback receives ETIMEDOUT.
+    fn write_timeout<CB: Into<crate::WriteCB<'static>>>(
+        &mut self,
+        bufs: &[impl crate::BufTrait],
+        timeout: u64,
+        cb: CB,
+    ) -> crate::Result<crate::WriteReq> {
+        let deadline = crate::Deadline::start(self.to_stream().into(), timeout)?;
+        let write_deadline = deadline.clone();
+        let mut cb = cb.into();
+        let result = self.write(
+            bufs,
+            move |req: crate::WriteReq, status: crate::Result<u32>| {
+                cb.call(req, write_deadline.finish(status))
+            },
+        );
+        if result.is_err() {
+            deadline.disarm();
+        }
+        result
+    }
+
     /// Extended write function for sending handles over a pipe. The pipe must be initialized with
     /// ipc == 1.
     ///
@@ -291,6 +455,7 @@ pub trait StreamTrait: ToStream {
         cb: CB,
     ) -> crate::Result<crate::WriteReq> {
         let mut req = crate::WriteReq::new(bufs, cb)?;
+        req.to_req().set_loop(&self.to_stream().get_loop());
         let result = crate::uvret(unsafe {
             uv_write2(
                 req.inner(),
@@ -1,4 +1,4 @@
-use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle};
+use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle, ToReq};
 use std::convert::{TryFrom, TryInto};
 use std::net::SocketAddr;
 use uv::{
@@ -236,6 +236,7 @@ impl TcpHandle {
         cb: CB,
     ) -> Result<crate::ConnectReq, Box<dyn std::error::Error>> {
         let mut req = crate::ConnectReq::new(cb)?;
+        req.to_req().set_loop(&self.get_loop());
         let mut sockaddr: uv::sockaddr_storage = unsafe { std::mem::zeroed() };
         crate::fill_sockaddr(&mut sockaddr, addr)?;
 
@@ -253,6 +254,29 @@ impl TcpHandle {
         result.map(|_| req).map_err(|e| Box::new(e) as _)
     }
 
+    /// Same as connect(), but gives up if the connection has not been established after timeout
+    /// milliseconds. In that case, the handle is closed and the callback receives ETIMEDOUT.
+    pub fn connect_timeout<CB: Into<crate::ConnectCB<'static>>>(
+        &mut self,
+        addr: &SocketAddr,
+        timeout: u64,
+        cb: CB,
+    ) -> Result<crate::ConnectReq, Box<dyn std::error::Error>> {
+        let deadline = crate::Deadline::start(self.to_handle(), timeout)?;
+        let connect_deadline = deadline.clone();
+        let mut cb = cb.into();
+        let result = self.connect(
+            addr,
+            move |req: crate::ConnectReq, status: crate::Result<u32>| {
+                cb.call(req, connect_deadline.finish(status))
+            },
+        );
+        if result.is_err() {
+            deadline.disarm();
+        }
+        result
+    }
+
     /// Resets a TCP connection by sending a RST packet. This is accomplished by setting the
     /// SO_LINGER socket option with a linger interval of zero and then calling close(). Due to
     /// some platform inconsistencies, mixing of shutdown() and close_reset() calls is not allowed.
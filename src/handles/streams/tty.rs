@@ -23,12 +23,19 @@ pub enum TtyMode {
 }
 
 /// Console virtual terminal mode type
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum VTermState {
     Supported = uv::uv_tty_vtermstate_t_UV_TTY_SUPPORTED as _,
     Unsupported = uv::uv_tty_vtermstate_t_UV_TTY_UNSUPPORTED as _,
 }
 
+/// Additional data to store on the stream
+#[derive(Default)]
+pub(crate) struct TtyDataFields {
+    pub(crate) resize_watcher: Option<crate::ResizeWatcher>,
+}
+
 /// TTY handles represent a stream for the console.
 #[derive(Clone, Copy)]
 pub struct TtyHandle {
@@ -64,11 +71,24 @@ impl TtyHandle {
             return Err(crate::Error::from_inner(ret as uv::uv_errno_t));
         }
 
-        crate::StreamHandle::initialize_data(uv_handle!(handle), super::NoAddlStreamData);
+        crate::StreamHandle::initialize_data(
+            uv_handle!(handle),
+            super::TtyData(Default::default()),
+        );
 
         Ok(TtyHandle { handle })
     }
 
+    /// Store the SIGWINCH watcher for on_resize(), dropping (and so closing) the previous one.
+    pub(crate) fn set_resize_watcher(&mut self, watcher: Option<crate::ResizeWatcher>) {
+        let dataptr = crate::StreamHandle::get_data(uv_handle!(self.handle));
+        if !dataptr.is_null() {
+            if let super::TtyData(d) = unsafe { &mut (*dataptr).addl } {
+                d.resize_watcher = watcher;
+            }
+        }
+    }
+
     /// Set the TTY using the specified terminal mode.
     pub fn set_mode(&mut self, mode: TtyMode) -> crate::Result<()> {
         crate::uvret(unsafe { uv_tty_set_mode(self.handle, mode as _) })
@@ -1,11 +1,11 @@
-use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle, NREAD};
+use crate::{FromInner, HandleTrait, Inner, IntoInner, ToHandle, ToReq, NREAD};
 use std::convert::{TryFrom, TryInto};
 use std::ffi::CString;
 use std::net::SocketAddr;
 use uv::{
     uv_buf_t, uv_udp_bind, uv_udp_connect, uv_udp_get_send_queue_count, uv_udp_get_send_queue_size,
-    uv_udp_getpeername, uv_udp_getsockname, uv_udp_init, uv_udp_init_ex, uv_udp_recv_start,
-    uv_udp_recv_stop, uv_udp_send, uv_udp_set_broadcast, uv_udp_set_membership,
+    uv_udp_getpeername, uv_udp_getsockname, uv_udp_init, uv_udp_init_ex, uv_udp_open,
+    uv_udp_recv_start, uv_udp_recv_stop, uv_udp_send, uv_udp_set_broadcast, uv_udp_set_membership,
     uv_udp_set_multicast_interface, uv_udp_set_multicast_loop, uv_udp_set_multicast_ttl,
     uv_udp_set_source_membership, uv_udp_set_ttl, uv_udp_t, uv_udp_try_send, uv_udp_try_send2,
     uv_udp_using_recvmmsg, AF_INET, AF_INET6, AF_UNSPEC,
@@ -178,6 +178,21 @@ impl UdpHandle {
         Ok(UdpHandle { handle })
     }
 
+    /// Opens an existing file descriptor or Windows SOCKET as a UDP handle.
+    ///
+    /// Unix only: The only requirement of the sock argument is that it follows the datagram
+    /// contract (works in unconnected mode, supports sendmsg()/recvmsg(), etc). In other words,
+    /// other datagram-type sockets like raw sockets or netlink sockets can also be passed to this
+    /// function.
+    ///
+    /// The file descriptor is set to non-blocking mode.
+    ///
+    /// Note: The passed file descriptor or SOCKET is not checked for its type, but it’s required
+    /// that it represents a valid datagram socket.
+    pub fn open(&mut self, socket: crate::Socket) -> crate::Result<()> {
+        crate::uvret(unsafe { uv_udp_open(self.handle, socket as _) })
+    }
+
     /// Bind the UDP handle to an IP address and port.
     pub fn bind(
         &mut self,
@@ -332,6 +347,7 @@ impl UdpHandle {
         cb: CB,
     ) -> Result<crate::UdpSendReq, Box<dyn std::error::Error>> {
         let mut req = crate::UdpSendReq::new(bufs, cb)?;
+        req.to_req().set_loop(&self.get_loop());
         let mut sockaddr: uv::sockaddr_storage = unsafe { std::mem::zeroed() };
         let mut sockaddr_ptr: *const uv::sockaddr = std::ptr::null();
         if let Some(addr) = addr {
@@ -1,5 +1,7 @@
 use crate::{FromInner, HandleTrait, Inner, IntoInner};
+use std::cell::Cell;
 use std::convert::TryFrom;
+use std::rc::Rc;
 use uv::{
     uv_timer_again, uv_timer_get_due_in, uv_timer_get_repeat, uv_timer_init, uv_timer_set_repeat,
     uv_timer_start, uv_timer_stop, uv_timer_t,
@@ -19,10 +21,15 @@ pub(crate) struct TimerDataFields<'a> {
 extern "C" fn uv_timer_cb(handle: *mut uv_timer_t) {
     let dataptr = crate::Handle::get_data(uv_handle!(handle));
     if !dataptr.is_null() {
-        unsafe {
-            if let super::TimerData(d) = &mut (*dataptr).addl {
-                d.timer_cb.call(handle.into_inner());
-            }
+        // The callback is taken out of the data while it runs: it may restart the timer with a new
+        // callback, which would otherwise drop the closure that is running.
+        let mut cb = match unsafe { &mut (*dataptr).addl } {
+            super::TimerData(d) => std::mem::take(&mut d.timer_cb),
+            _ => return,
+        };
+        cb.call(handle.into_inner());
+        if let super::TimerData(d) = unsafe { &mut (*dataptr).addl } {
+            d.timer_cb.restore(cb);
         }
     }
 }
@@ -125,6 +132,26 @@ impl TimerHandle {
     pub fn due_in(&self) -> u64 {
         unsafe { uv_timer_get_due_in(self.handle) }
     }
+
+    /// The loop time when the timer is due, and the order in which the timer was started. Timers
+    /// that are due at the same time run in the order they were started.
+    #[cfg(feature = "testing")]
+    pub(crate) fn schedule(&self) -> (u64, u64) {
+        unsafe { ((*self.handle).timeout, (*self.handle).start_id) }
+    }
+
+    /// Run the timer the same way that libuv does when it expires: the timer is stopped, restarted
+    /// if it is repeating, and then the callback is called.
+    #[cfg(feature = "testing")]
+    pub(crate) fn expire(&mut self) {
+        unsafe {
+            uv_timer_stop(self.handle);
+            uv_timer_again(self.handle);
+            if let Some(cb) = (*self.handle).timer_cb {
+                cb(self.handle);
+            }
+        }
+    }
 }
 
 impl FromInner<*mut uv_timer_t> for TimerHandle {
@@ -166,6 +193,60 @@ impl TryFrom<crate::Handle> for TimerHandle {
 
 impl HandleTrait for TimerHandle {}
 
+/// A timer that closes a handle if an operation on the handle does not finish in time. Used to
+/// implement the timeout variants of connect() and write().
+#[derive(Clone)]
+pub(crate) struct Deadline {
+    timer: TimerHandle,
+    expired: Rc<Cell<bool>>,
+}
+
+impl Deadline {
+    /// Close handle after timeout milliseconds, unless the deadline is disarmed first.
+    pub(crate) fn start(handle: crate::Handle, timeout: u64) -> crate::Result<Deadline> {
+        let mut timer = TimerHandle::new(&handle.get_loop())?;
+        let expired = Rc::new(Cell::new(false));
+        let timer_expired = expired.clone();
+        let result = timer.start(timeout, 0, move |mut timer: TimerHandle| {
+            timer_expired.set(true);
+            let mut handle = handle;
+            if !handle.is_closing() {
+                handle.close(());
+            }
+            timer.close(());
+        });
+        if let Err(e) = result {
+            timer.close(());
+            return Err(e);
+        }
+        Ok(Deadline { timer, expired })
+    }
+
+    /// Stop and close the timer, if it has not already expired.
+    pub(crate) fn disarm(&self) {
+        // once expired, the timer is closed and may already be freed
+        if !self.expired.get() {
+            let mut timer = self.timer;
+            if !timer.is_closing() {
+                timer.close(());
+            }
+        }
+    }
+
+    /// Called when the operation completes: disarms the timer and, if the deadline expired first,
+    /// turns the ECANCELED error that closing the handle produced into ETIMEDOUT.
+    pub(crate) fn finish<T>(&self, status: crate::Result<T>) -> crate::Result<T> {
+        if !self.expired.get() {
+            self.disarm();
+            return status;
+        }
+        match status {
+            Err(crate::Error::ECANCELED) => Err(crate::Error::ETIMEDOUT),
+            status => status,
+        }
+    }
+}
+
 impl crate::Loop {
     /// Create and initialize a new timer handle
     pub fn timer(&self) -> crate::Result<TimerHandle> {
@@ -0,0 +1,206 @@
+use crate::{HandleTrait, StreamTrait, ToStream};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Events reported by a BufferedWriter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WriterEvent {
+    /// The amount of queued data reached the high watermark. If a source is attached, reading
+    /// from it has been stopped.
+    Paused,
+
+    /// The amount of queued data fell to the low watermark. If a source is attached, reading from
+    /// it has been restarted.
+    Drained,
+
+    /// A queued write failed.
+    Error(crate::Error),
+}
+
+callbacks! {
+    pub WriterEventCB(writer: BufferedWriter, event: WriterEvent);
+}
+
+struct WriterState {
+    stream: crate::StreamHandle,
+    source: Option<crate::StreamHandle>,
+    high_watermark: usize,
+    low_watermark: usize,
+    paused: bool,
+    event_cb: WriterEventCB<'static>,
+    events: VecDeque<WriterEvent>,
+    emitting: bool,
+}
+
+/// Adds flow control to writes on a stream, in the same way that stream.pipe() does in Node.js.
+///
+/// The watermarks are checked against the stream's write queue size: the number of bytes that
+/// libuv is holding because the stream could not accept them yet. When that amount reaches the
+/// high watermark, the writer is "paused": write() returns false and a WriterEvent::Paused event
+/// is reported. Once enough data has been flushed that the amount falls to the low watermark, a
+/// WriterEvent::Drained event is reported. Writes are never rejected - it is up to the producer to
+/// stop writing while the writer is paused.
+///
+/// Data that is written to the stream directly, rather than through the writer, counts towards
+/// the watermarks as well. However, the writer only checks whether the queue has drained when one
+/// of its own writes finishes.
+///
+/// If a source stream is attached with source(), reading from the source is stopped while the
+/// writer is paused and restarted when it drains. The source must already be reading with
+/// read_start().
+#[derive(Clone)]
+pub struct BufferedWriter {
+    state: Rc<RefCell<WriterState>>,
+}
+
+impl BufferedWriter {
+    /// Create a new BufferedWriter for the given stream, with a high watermark of 16KiB and a low
+    /// watermark of 0 (ie, "drained" means the queue is empty).
+    pub fn new(stream: &impl ToStream) -> BufferedWriter {
+        BufferedWriter {
+            state: Rc::new(RefCell::new(WriterState {
+                stream: stream.to_stream(),
+                source: None,
+                high_watermark: 16 * 1024,
+                low_watermark: 0,
+                paused: false,
+                event_cb: ().into(),
+                events: VecDeque::new(),
+                emitting: false,
+            })),
+        }
+    }
+
+    /// Set the high and low watermarks, in bytes. low must be less than high.
+    pub fn watermarks(self, high: usize, low: usize) -> BufferedWriter {
+        {
+            let mut state = self.state.borrow_mut();
+            state.high_watermark = high.max(1);
+            state.low_watermark = low.min(state.high_watermark - 1);
+        }
+        self
+    }
+
+    /// Set the callback for writer events.
+    pub fn on_event<CB: Into<WriterEventCB<'static>>>(self, cb: CB) -> BufferedWriter {
+        self.state.borrow_mut().event_cb = cb.into();
+        self
+    }
+
+    /// Stop reading from source while the writer is paused, and restart reading when it drains.
+    pub fn source(self, source: &impl ToStream) -> BufferedWriter {
+        self.state.borrow_mut().source = Some(source.to_stream());
+        self
+    }
+
+    /// The stream that is being written to.
+    pub fn stream(&self) -> crate::StreamHandle {
+        self.state.borrow().stream
+    }
+
+    /// The number of bytes that have been written, but not yet flushed. This is the stream's write
+    /// queue size.
+    pub fn queued(&self) -> usize {
+        self.stream().get_write_queue_size()
+    }
+
+    /// Returns true if the amount of queued data has reached the high watermark and has not yet
+    /// fallen back to the low watermark.
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    /// Queue data to be written to the stream. The data is copied, so it can be dropped as soon as
+    /// write() returns.
+    ///
+    /// Returns false if the writer is paused, in which case the producer should wait for a
+    /// WriterEvent::Drained event before writing more.
+    pub fn write(&self, data: &[u8]) -> Result<bool, Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(!self.is_paused());
+        }
+
+        let mut stream = self.stream();
+        let writer = self.clone();
+        crate::write_copy(&mut stream, data, move |status| writer.on_write(status))?;
+
+        let pause = {
+            let state = self.state.borrow();
+            !state.paused && stream.get_write_queue_size() >= state.high_watermark
+        };
+        if pause {
+            self.set_paused(true);
+        }
+        Ok(!self.is_paused())
+    }
+
+    /// Called when a queued write has finished.
+    fn on_write(&self, status: crate::Result<u32>) {
+        let drain = {
+            let state = self.state.borrow();
+            state.paused && state.stream.get_write_queue_size() <= state.low_watermark
+        };
+        if let Err(e) = status {
+            self.emit(WriterEvent::Error(e));
+        }
+        if drain {
+            self.set_paused(false);
+        }
+    }
+
+    /// Pause or resume, stopping or restarting the source, and report the event.
+    fn set_paused(&self, paused: bool) {
+        let source = {
+            let mut state = self.state.borrow_mut();
+            state.paused = paused;
+            state.source
+        };
+
+        if let Some(mut source) = source {
+            // a source that has reached EOF is no longer readable and must not be restarted
+            if !source.is_closing() {
+                let _ = if paused {
+                    source.read_stop()
+                } else if source.is_readable() {
+                    source.read_resume()
+                } else {
+                    Ok(())
+                };
+            }
+        }
+
+        self.emit(if paused {
+            WriterEvent::Paused
+        } else {
+            WriterEvent::Drained
+        });
+    }
+
+    /// Call the event callback. The callback is taken out of the state while it runs so that it
+    /// can use the writer. Events that happen while the callback is running (for example, if it
+    /// writes enough data to pause the writer again) are queued and reported once it returns.
+    fn emit(&self, event: WriterEvent) {
+        let mut cb = {
+            let mut state = self.state.borrow_mut();
+            state.events.push_back(event);
+            if state.emitting {
+                return;
+            }
+            state.emitting = true;
+            std::mem::take(&mut state.event_cb)
+        };
+
+        loop {
+            let event = self.state.borrow_mut().events.pop_front();
+            match event {
+                Some(event) => cb.call(self.clone(), event),
+                None => break,
+            }
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.emitting = false;
+        state.event_cb.restore(cb);
+    }
+}
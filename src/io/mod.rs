@@ -0,0 +1,5 @@
+pub mod buffered_writer;
+pub use buffered_writer::*;
+
+pub mod piping;
+pub use piping::*;
@@ -0,0 +1,201 @@
+use crate::{BufferedWriter, HandleTrait, StreamTrait, ToStream, WriterEvent};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+callbacks! {
+    pub PipeCB(bytes: u64, status: crate::Result<()>);
+    pub ProxyCB(a_to_b: u64, b_to_a: u64, status: crate::Result<()>);
+}
+
+/// Called when piping has finished, with the number of bytes forwarded in each direction.
+type DoneCB = Box<dyn FnOnce(&[u64], crate::Result<()>)>;
+
+/// The state shared by the directions of a pipe_streams() or proxy().
+struct Session {
+    streams: Vec<crate::StreamHandle>,
+    bytes: Vec<u64>,
+    remaining: usize,
+    close_on_success: bool,
+    done_cb: Option<DoneCB>,
+}
+
+type State = Rc<RefCell<Session>>;
+
+/// Stop piping because of an error: close all of the streams and report the error.
+fn fail(state: &State, error: crate::Error) {
+    let (streams, bytes, done_cb) = {
+        let mut s = state.borrow_mut();
+        let done_cb = match s.done_cb.take() {
+            Some(done_cb) => done_cb,
+            None => return,
+        };
+        (s.streams.clone(), s.bytes.clone(), done_cb)
+    };
+
+    for mut stream in streams {
+        if !stream.is_closing() {
+            stream.close(());
+        }
+    }
+    done_cb(&bytes, Err(error));
+}
+
+/// Called when one direction has finished successfully.
+fn finish_direction(state: &State) {
+    let (streams, bytes, done_cb) = {
+        let mut s = state.borrow_mut();
+        s.remaining -= 1;
+        if s.remaining > 0 || s.done_cb.is_none() {
+            return;
+        }
+        let streams = if s.close_on_success {
+            s.streams.clone()
+        } else {
+            Vec::new()
+        };
+        (streams, s.bytes.clone(), s.done_cb.take())
+    };
+
+    for mut stream in streams {
+        if !stream.is_closing() {
+            stream.close(());
+        }
+    }
+    if let Some(done_cb) = done_cb {
+        done_cb(&bytes, Ok(()));
+    }
+}
+
+/// Forward everything read from src to dst. The BufferedWriter stops reading from src whenever
+/// dst's write queue fills up. When src reaches EOF, dst is shut down.
+fn forward(
+    state: &State,
+    index: usize,
+    mut src: crate::StreamHandle,
+    dst: crate::StreamHandle,
+) -> crate::Result<()> {
+    let writer_state = state.clone();
+    let writer = BufferedWriter::new(&dst)
+        .watermarks(64 * 1024, 16 * 1024)
+        .source(&src)
+        .on_event(move |_, event: WriterEvent| {
+            if let WriterEvent::Error(e) = event {
+                fail(&writer_state, e);
+            }
+        });
+
+    let read_state = state.clone();
+    src.read_start(
+        |_, size| crate::Buf::with_capacity(size).ok(),
+        move |mut src: crate::StreamHandle,
+              nread: crate::Result<usize>,
+              mut buf: crate::ReadonlyBuf| {
+            match nread {
+                Ok(0) => {}
+                Ok(len) => match writer.write(&buf[..len]) {
+                    Ok(_) => read_state.borrow_mut().bytes[index] += len as u64,
+                    Err(e) => {
+                        let e = match e.downcast_ref::<crate::Error>() {
+                            Some(e) => *e,
+                            None => crate::Error::ENOMEM,
+                        };
+                        fail(&read_state, e);
+                    }
+                },
+                Err(crate::Error::EOF) => {
+                    let _ = src.read_stop();
+
+                    // shutdown waits for the pending writes to finish
+                    let shutdown_state = read_state.clone();
+                    let result = writer
+                        .stream()
+                        .shutdown(move |_, status: crate::Result<u32>| match status {
+                            Ok(_) => finish_direction(&shutdown_state),
+                            Err(e) => fail(&shutdown_state, e),
+                        });
+                    if let Err(e) = result {
+                        fail(&read_state, e);
+                    }
+                }
+                Err(e) => fail(&read_state, e),
+            }
+            buf.dealloc();
+        },
+    )
+}
+
+/// Start piping in one or more directions.
+fn start(
+    directions: &[(crate::StreamHandle, crate::StreamHandle)],
+    streams: Vec<crate::StreamHandle>,
+    close_on_success: bool,
+    done_cb: DoneCB,
+) -> crate::Result<()> {
+    let state = Rc::new(RefCell::new(Session {
+        streams,
+        bytes: vec![0; directions.len()],
+        remaining: directions.len(),
+        close_on_success,
+        done_cb: Some(done_cb),
+    }));
+
+    for (index, (src, dst)) in directions.iter().enumerate() {
+        if let Err(e) = forward(&state, index, *src, *dst) {
+            // undo the directions that were already started
+            state.borrow_mut().done_cb = None;
+            for (mut src, _) in directions.iter().take(index).copied() {
+                let _ = src.read_stop();
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Forward all data read from src to dst, like `src | dst` in a shell.
+///
+/// Reading from src is stopped whenever the write queue of dst fills up, and restarted once it
+/// drains. When src reaches EOF, dst is shut down and the callback is called with the number of
+/// bytes forwarded; neither stream is closed. If reading or writing fails, both streams are
+/// closed and the callback receives the error.
+///
+/// src must not already be reading.
+pub fn pipe_streams<CB: Into<PipeCB<'static>>>(
+    src: &impl ToStream,
+    dst: &impl ToStream,
+    cb: CB,
+) -> crate::Result<()> {
+    let src = src.to_stream();
+    let dst = dst.to_stream();
+    let mut cb = cb.into();
+    start(
+        &[(src, dst)],
+        vec![src, dst],
+        false,
+        Box::new(move |bytes, status| cb.call(bytes[0], status)),
+    )
+}
+
+/// Forward data in both directions between two streams, with the same flow control as
+/// pipe_streams().
+///
+/// EOF on one stream shuts down the other. Once both directions have finished, both streams are
+/// closed and the callback is called with the number of bytes forwarded in each direction. If
+/// reading or writing fails, both streams are closed and the callback receives the error.
+///
+/// Neither stream may already be reading.
+pub fn proxy<CB: Into<ProxyCB<'static>>>(
+    a: &impl ToStream,
+    b: &impl ToStream,
+    cb: CB,
+) -> crate::Result<()> {
+    let a = a.to_stream();
+    let b = b.to_stream();
+    let mut cb = cb.into();
+    start(
+        &[(a, b), (b, a)],
+        vec![a, b],
+        true,
+        Box::new(move |bytes, status| cb.call(bytes[0], bytes[1], status)),
+    )
+}
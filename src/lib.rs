@@ -67,6 +67,15 @@ macro_rules! __callback {
                     _ => false
                 }
             }
+
+            /// Put back a callback that was taken out of self while it ran, unless self was given
+            /// a new callback in the meantime.
+            #[allow(dead_code)]
+            pub(crate) fn restore(&mut self, cb: $Name<'a>) {
+                if self.is_nil() {
+                    *self = cb;
+                }
+            }
         }
 
         impl<'a> Default for $Name<'a> {
@@ -80,6 +89,7 @@ macro_rules! __callback {
 macro_rules! __callback_callfn {
     ($Name:ident($($($a:ident: $T:ty),+)?) -> $TReturn:ty) => {
         pub(crate) fn call(&mut self$(,$($a: $T),+)?) -> $TReturn {
+            __callback_trace!($Name($($($a),+)?));
             match self {
                 $Name::CB(ref mut f) => f($($($a),+)?),
                 $Name::Nil => Default::default(),
@@ -88,6 +98,7 @@ macro_rules! __callback_callfn {
     };
     ($Name:ident($($($a:ident: $T:ty),+)?)) => {
         pub(crate) fn call(&mut self$(,$($a: $T),+)?) {
+            __callback_trace!($Name($($($a),+)?));
             match self {
                 $Name::CB(ref mut f) => f($($($a),+)?),
                 $Name::Nil => (),
@@ -96,6 +107,29 @@ macro_rules! __callback_callfn {
     }
 }
 
+#[cfg(feature = "tracing")]
+macro_rules! __callback_trace {
+    ($Name:ident($($($a:ident),+)?)) => {
+        #[allow(unused_imports)]
+        use crate::trace::{
+            IgnoreHandle as _, IgnoreReq as _, IgnoreStatus as _, RecordHandle as _,
+            RecordReq as _, RecordStatus as _,
+        };
+        let span = crate::trace::callback_span(stringify!($Name));
+        $($(
+            (&crate::trace::Arg(&$a)).record_handle(&span);
+            (&crate::trace::Arg(&$a)).record_req(&span);
+            (&crate::trace::Arg(&$a)).record_status(&span);
+        )+)?
+        let _timed = crate::trace::Timed::enter(span);
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! __callback_trace {
+    ($Name:ident($($($a:ident),+)?)) => {};
+}
+
 macro_rules! use_c_callback {
     ($ccb:expr, $cb:expr) => {
         if ($cb).is_nil() {
@@ -122,6 +156,9 @@ fn uvret(code: ::std::os::raw::c_int) -> Result<()> {
 mod inner;
 use inner::*;
 
+#[cfg(feature = "tracing")]
+mod trace;
+
 pub mod error;
 pub use error::Error::*;
 pub use error::*;
@@ -144,6 +181,18 @@ pub use net::*;
 pub mod handles;
 pub use handles::*;
 
+pub mod io;
+pub use io::*;
+
+pub mod term;
+pub use term::*;
+
+pub mod signals;
+pub use signals::*;
+
+pub mod timers;
+pub use timers::*;
+
 pub mod requests;
 pub use requests::*;
 
@@ -153,6 +202,14 @@ pub use shared_libs::*;
 pub mod misc;
 pub use misc::*;
 
+pub mod metrics;
+pub use metrics::*;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "testing")]
+pub use testing::*;
+
 /// Imports some things that most every program will need.
 pub mod prelude {
     pub use super::{
@@ -1,11 +1,15 @@
-use crate::{FromInner, HandleTrait, IntoInner};
+use crate::{FromInner, HandleTrait, HandleType, Inner, IntoInner, StreamTrait, ToHandle};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
 use uv::{
     uv_backend_fd, uv_backend_timeout, uv_default_loop, uv_handle_t, uv_loop_alive, uv_loop_close,
     uv_loop_configure, uv_loop_delete, uv_loop_fork, uv_loop_get_data, uv_loop_init, uv_loop_new,
     uv_loop_option_UV_LOOP_BLOCK_SIGNAL, uv_loop_option_UV_LOOP_USE_IO_URING_SQPOLL,
     uv_loop_option_UV_METRICS_IDLE_TIME, uv_loop_set_data, uv_loop_t, uv_metrics_idle_time,
     uv_metrics_info, uv_metrics_t, uv_now, uv_run, uv_run_mode, uv_run_mode_UV_RUN_DEFAULT,
-    uv_run_mode_UV_RUN_NOWAIT, uv_run_mode_UV_RUN_ONCE, uv_stop, uv_update_time, uv_walk,
+    uv_run_mode_UV_RUN_NOWAIT, uv_run_mode_UV_RUN_ONCE, uv_stop, uv_stream_t, uv_update_time,
+    uv_walk,
 };
 
 /// Mode used to run the loop.
@@ -63,12 +67,27 @@ impl FromInner<uv_metrics_t> for Metrics {
 #[derive(Default)]
 pub(crate) struct LoopData {
     walk_cb: Option<Box<dyn FnMut(crate::Handle)>>,
+
+    /// The number of requests, by type, that were started on the loop and have not been
+    /// destroyed yet. Each started request holds a reference to it, so that a request destroyed
+    /// after the loop can still update it.
+    pending_reqs: crate::PendingReqs,
+
+    #[cfg(feature = "testing")]
+    fs_faults: RefCell<crate::testing::fs_faults::FsFaults>,
+
+    #[cfg(feature = "tracing")]
+    iterations: RefCell<crate::trace::Iterations>,
 }
 
 /// Callback for uv_walk
 extern "C" fn uv_walk_cb(handle: *mut uv_handle_t, _: *mut ::std::os::raw::c_void) {
     let handle: crate::Handle = handle.into_inner();
     let r#loop = handle.get_loop();
+    #[cfg(feature = "tracing")]
+    if crate::trace::is_iteration_check(&r#loop, handle.inner()) {
+        return;
+    }
     let dataptr = r#loop.get_data();
     if !dataptr.is_null() {
         unsafe {
@@ -79,6 +98,36 @@ extern "C" fn uv_walk_cb(handle: *mut uv_handle_t, _: *mut ::std::os::raw::c_voi
     }
 }
 
+/// Closes a handle. Writable streams are shutdown() first so that pending writes are flushed; the
+/// stream is closed when the shutdown completes.
+fn close_gracefully(mut handle: crate::Handle) {
+    if handle.is_closing() {
+        return;
+    }
+
+    match handle.get_type() {
+        HandleType::TCP | HandleType::NAMED_PIPE | HandleType::TTY => {
+            let ptr: *mut uv_handle_t = handle.inner();
+            let mut stream = crate::StreamHandle::from_inner(ptr as *mut uv_stream_t);
+            if stream.is_writable() {
+                // the shutdown is canceled if the stream is closed before it finishes
+                let result = stream.shutdown(|req: crate::ShutdownReq, _| {
+                    let mut stream = req.handle();
+                    if !stream.is_closing() {
+                        stream.close(());
+                    }
+                });
+                if result.is_ok() {
+                    return;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    handle.close(());
+}
+
 /// The event loop is the central part of libuv’s functionality. It takes care of polling for i/o
 /// and scheduling callbacks to be run based on different sources of events.
 pub struct Loop {
@@ -158,7 +207,8 @@ impl Loop {
     ///
     /// This operation is currently only implemented for SIGPROF signals, to suppress unnecessary
     /// wakeups when using a sampling profiler. Requesting other signals will fail with UV_EINVAL.
-    pub fn block_signal(&mut self, signum: i32) -> crate::Result<()> {
+    pub fn block_signal<S: Into<crate::Signal>>(&mut self, signal: S) -> crate::Result<()> {
+        let signum = signal.into().as_raw();
         crate::uvret(unsafe {
             uv_loop_configure(self.handle, uv_loop_option_UV_LOOP_BLOCK_SIGNAL, signum)
         })
@@ -183,18 +233,40 @@ impl Loop {
     /// Error::EBUSY.  After this function returns, the user can free the memory allocated for the
     /// loop.
     pub fn close(&mut self) -> crate::Result<()> {
+        #[cfg(feature = "tracing")]
+        crate::trace::close_iterations(self);
+
         crate::uvret(unsafe { uv_loop_close(self.handle) })
     }
 
     /// This function runs the event loop. It will act differently depending on the specified mode.
     /// run() is not reentrant. It must not be called from a callback.
+    ///
+    /// With the tracing feature enabled, each call to run() is recorded as a span containing the
+    /// number of loop iterations that ran, and each iteration is recorded as a child span. An
+    /// iteration's span ends after the loop runs its check handles, so the callbacks of closing
+    /// handles and of timers, which libuv runs at the end of an iteration, are recorded in the
+    /// span of the next one.
     pub fn run(&mut self, mode: RunMode) -> crate::Result<i32> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::RunSpan::enter(self, &mode);
+        #[cfg(feature = "tracing")]
+        crate::trace::enter_iterations(self);
+
         let ret = unsafe { uv_run(self.handle, mode.into_inner()) };
-        if ret < 0 {
+
+        #[cfg(feature = "tracing")]
+        crate::trace::exit_iterations(self);
+        let result = if ret < 0 {
             Err(crate::Error::from_inner(ret as uv::uv_errno_t))
         } else {
             Ok(ret)
-        }
+        };
+
+        #[cfg(feature = "tracing")]
+        span.record_result(&result);
+
+        result
     }
 
     /// Returns true if there are referenced active handles, active requests or closing handles in
@@ -264,6 +336,37 @@ impl Loop {
         unsafe { uv_update_time(self.handle) }
     }
 
+    /// Overwrite the event loop's concept of "now". This is used by VirtualClock.
+    #[cfg(feature = "testing")]
+    pub(crate) fn set_time(&mut self, now: u64) {
+        unsafe { (*self.handle).time = now };
+    }
+
+    /// The fault rules for fs_* functions on this loop.
+    #[cfg(feature = "testing")]
+    pub(crate) fn fs_faults(&self) -> Option<&RefCell<crate::testing::fs_faults::FsFaults>> {
+        unsafe { self.get_data().as_ref() }.map(|data| &data.fs_faults)
+    }
+
+    /// The spans for the iterations of this loop.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn iterations(&self) -> Option<&RefCell<crate::trace::Iterations>> {
+        unsafe { self.get_data().as_ref() }.map(|data| &data.iterations)
+    }
+
+    /// Returns the number of requests, by type, that were started on this loop and have not been
+    /// destroyed yet.
+    pub(crate) fn pending_reqs(&self) -> HashMap<crate::ReqType, usize> {
+        unsafe { self.get_data().as_ref() }
+            .map(|data| data.pending_reqs.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// The counts of pending requests, which requests started on this loop hold on to.
+    pub(crate) fn pending_reqs_counter(&self) -> Option<crate::PendingReqs> {
+        unsafe { self.get_data().as_ref() }.map(|data| data.pending_reqs.clone())
+    }
+
     /// Walk the list of handles.
     pub fn walk(&self, cb: impl FnMut(crate::Handle) + 'static) {
         let cb = Box::new(cb);
@@ -275,6 +378,72 @@ impl Loop {
         unsafe { uv_walk(self.handle, Some(uv_walk_cb), std::ptr::null_mut()) };
     }
 
+    /// Start closing every handle in the loop. Writable streams are shutdown() first, which waits
+    /// for pending writes to complete, and are closed when the shutdown finishes. All other
+    /// handles are closed immediately. Handles that are already closing are left alone.
+    ///
+    /// This function does not run the loop - the handles will finish closing during the following
+    /// loop iterations. See shutdown_gracefully().
+    pub fn close_all(&self) {
+        self.walk(close_gracefully);
+    }
+
+    /// Calls close_all(), runs the loop until all handles have closed, and then close()'s the
+    /// loop. timeout is in milliseconds. Loops created with new() are not close()'d since that
+    /// happens when they are dropped.
+    ///
+    /// If some handles are still open when the timeout expires (a stream that cannot flush its
+    /// pending writes, for example), they are forcibly closed and a ShutdownError listing their
+    /// types is returned. Pending requests, such as file system operations running in the
+    /// threadpool, will cause close() to fail with EBUSY.
+    ///
+    /// Like run(), this function must not be called from a callback.
+    pub fn shutdown_gracefully(&mut self, timeout: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.close_all();
+
+        // the deadline timer is unref'd so that it doesn't keep the loop alive by itself
+        let expired = Rc::new(Cell::new(false));
+        let mut deadline = self.timer()?;
+        let expired_cb = expired.clone();
+        deadline.start(timeout, 0, move |timer: crate::TimerHandle| {
+            expired_cb.set(true);
+            timer.get_loop().stop();
+        })?;
+        deadline.unref();
+        self.run(RunMode::Default)?;
+
+        let lingering = Rc::new(RefCell::new(Vec::new()));
+        if expired.get() {
+            let deadline_ptr: *mut uv_handle_t = deadline.to_handle().inner();
+            let lingering = lingering.clone();
+            self.walk(move |mut handle| {
+                if handle.inner() != deadline_ptr && !handle.is_closing() {
+                    lingering.borrow_mut().push(handle.get_type());
+                    handle.close(());
+                }
+            });
+        }
+
+        // closing handles are finished at the end of the loop iteration
+        deadline.close(());
+        self.run(RunMode::NoWait)?;
+
+        // loops created with new() are closed when they are dropped - closing them twice is an
+        // assertion failure in libuv
+        let result = if !self.should_drop {
+            self.close()
+        } else if self.is_alive() {
+            Err(crate::Error::EBUSY)
+        } else {
+            Ok(())
+        };
+        let lingering = lingering.replace(Vec::new());
+        if !lingering.is_empty() {
+            return Err(Box::new(crate::ShutdownError::new(lingering)));
+        }
+        result.map_err(|e| Box::new(e) as _)
+    }
+
     /// Reinitialize any kernel state necessary in the child process after a fork(2) system call.
     ///
     /// Previously started watchers will continue to be started in the child process.
@@ -337,6 +506,9 @@ impl Drop for Loop {
     fn drop(&mut self) {
         if self.should_drop {
             if !self.handle.is_null() {
+                #[cfg(feature = "tracing")]
+                crate::trace::close_iterations(self);
+
                 self.free_data();
                 unsafe { uv_loop_delete(self.handle) };
             }
@@ -0,0 +1,322 @@
+use crate::{HandleTrait, HandleType, Inner, ReqType, ToHandle};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::rc::Rc;
+use uv::uv_handle_t;
+
+/// A point-in-time view of an event loop's metrics, returned by MetricsCollector::snapshot().
+#[derive(Clone, Debug, Default)]
+pub struct MetricsSnapshot {
+    /// Number of event loop iterations.
+    pub loop_count: u64,
+
+    /// Number of events that have been processed by the event handler.
+    pub events: u64,
+
+    /// Number of events that were waiting to be processed when the event provider was called.
+    pub events_waiting: u64,
+
+    /// Accumulated time, in nanoseconds, that the loop has spent idle in the kernel's event
+    /// provider.
+    pub idle_time: u64,
+
+    /// The number of open handles, by type. Handles that are closing are not counted, nor are the
+    /// handles used by the MetricsCollector itself.
+    pub handles: HashMap<HandleType, usize>,
+
+    /// The number of requests, by type, that are still pending. Note that libuv does not track
+    /// requests by type, so this counts the requests that were started on the loop through this
+    /// crate and have not been destroyed yet.
+    pub requests: HashMap<ReqType, usize>,
+
+    /// The number of active timers.
+    pub timers: usize,
+
+    /// The number of active timers that are already due but have not run yet.
+    pub timers_overdue: usize,
+
+    /// The time, in nanoseconds, the most recent loop iteration spent running callbacks. This is
+    /// the duration of the iteration minus the time spent idle in the event provider.
+    pub iteration_latency: u64,
+
+    /// The largest iteration_latency seen since the collector was created.
+    pub iteration_latency_max: u64,
+}
+
+impl MetricsSnapshot {
+    /// Render the snapshot in the Prometheus text exposition format. All metric names are prefixed
+    /// with "libuv_loop_".
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "iterations_total",
+            "counter",
+            "Number of event loop iterations.",
+        );
+        let _ = writeln!(out, "libuv_loop_iterations_total {}", self.loop_count);
+        write_metric(
+            &mut out,
+            "events_total",
+            "counter",
+            "Number of events processed.",
+        );
+        let _ = writeln!(out, "libuv_loop_events_total {}", self.events);
+        write_metric(
+            &mut out,
+            "events_waiting_total",
+            "counter",
+            "Number of events that were waiting when the event provider was called.",
+        );
+        let _ = writeln!(
+            out,
+            "libuv_loop_events_waiting_total {}",
+            self.events_waiting
+        );
+        write_metric(
+            &mut out,
+            "idle_seconds_total",
+            "counter",
+            "Time spent idle in the event provider.",
+        );
+        let _ = writeln!(
+            out,
+            "libuv_loop_idle_seconds_total {}",
+            seconds(self.idle_time)
+        );
+
+        write_metric(
+            &mut out,
+            "handles",
+            "gauge",
+            "Number of open handles by type.",
+        );
+        let mut handles: Vec<(String, usize)> =
+            self.handles.iter().map(|(t, n)| (t.name(), *n)).collect();
+        handles.sort();
+        for (name, count) in handles {
+            let _ = writeln!(out, "libuv_loop_handles{{type=\"{}\"}} {}", name, count);
+        }
+
+        write_metric(
+            &mut out,
+            "requests",
+            "gauge",
+            "Number of pending requests by type.",
+        );
+        let mut requests: Vec<(String, usize)> =
+            self.requests.iter().map(|(t, n)| (t.name(), *n)).collect();
+        requests.sort();
+        for (name, count) in requests {
+            let _ = writeln!(out, "libuv_loop_requests{{type=\"{}\"}} {}", name, count);
+        }
+
+        write_metric(&mut out, "timers", "gauge", "Number of active timers.");
+        let _ = writeln!(out, "libuv_loop_timers {}", self.timers);
+        write_metric(
+            &mut out,
+            "timers_overdue",
+            "gauge",
+            "Number of active timers that are due but have not run.",
+        );
+        let _ = writeln!(out, "libuv_loop_timers_overdue {}", self.timers_overdue);
+        write_metric(
+            &mut out,
+            "iteration_latency_seconds",
+            "gauge",
+            "Time the last loop iteration spent running callbacks.",
+        );
+        let _ = writeln!(
+            out,
+            "libuv_loop_iteration_latency_seconds {}",
+            seconds(self.iteration_latency)
+        );
+        write_metric(
+            &mut out,
+            "iteration_latency_max_seconds",
+            "gauge",
+            "Largest time a loop iteration spent running callbacks.",
+        );
+        let _ = writeln!(
+            out,
+            "libuv_loop_iteration_latency_max_seconds {}",
+            seconds(self.iteration_latency_max)
+        );
+        out
+    }
+}
+
+/// Writes the HELP and TYPE lines for a metric.
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP libuv_loop_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE libuv_loop_{} {}", name, kind);
+}
+
+/// Converts nanoseconds to fractional seconds.
+fn seconds(ns: u64) -> f64 {
+    ns as f64 / 1e9
+}
+
+/// Values sampled by the prepare and check handles.
+#[derive(Default)]
+struct Samples {
+    metrics: Option<crate::Metrics>,
+    last_check: Option<(u64, u64)>,
+    iteration_latency: u64,
+    iteration_latency_max: u64,
+}
+
+/// Collects metrics about an event loop. Event loop metrics are sampled once per loop iteration
+/// using a PrepareHandle and a CheckHandle. Handle and timer counts are gathered when snapshot() is
+/// called. Both handles are unref'd so the collector will not keep the loop alive.
+///
+/// The collector must be close()'d when it is no longer needed.
+pub struct MetricsCollector {
+    prepare: crate::PrepareHandle,
+    check: crate::CheckHandle,
+    samples: Rc<RefCell<Samples>>,
+}
+
+impl MetricsCollector {
+    /// Create a new MetricsCollector for the given loop. This will configure the loop to
+    /// accumulate_idle_time().
+    pub fn new(r#loop: &mut crate::Loop) -> crate::Result<MetricsCollector> {
+        r#loop.accumulate_idle_time()?;
+
+        let samples: Rc<RefCell<Samples>> = Rc::new(RefCell::new(Default::default()));
+        let mut prepare = r#loop.prepare()?;
+        let mut check = match r#loop.check() {
+            Ok(check) => check,
+            Err(e) => {
+                prepare.close(());
+                return Err(e);
+            }
+        };
+
+        // libuv recommends retrieving metrics_info() in a prepare callback to make sure there are
+        // no inconsistencies with the counters.
+        let prepare_samples = samples.clone();
+        let result = prepare.start(move |handle: crate::PrepareHandle| {
+            if let Ok(metrics) = handle.get_loop().metrics_info() {
+                prepare_samples.borrow_mut().metrics = Some(metrics);
+            }
+        });
+
+        // The check callback runs right after polling for i/o. The time between two checks is a
+        // full loop iteration; whatever part of that wasn't spent idle in the event provider was
+        // spent running callbacks.
+        let check_samples = samples.clone();
+        let result = result.and_then(|_| {
+            check.start(move |handle: crate::CheckHandle| {
+                let now = crate::hrtime();
+                let idle = handle.get_loop().idle_time();
+                let mut samples = check_samples.borrow_mut();
+                if let Some((last_now, last_idle)) = samples.last_check {
+                    let elapsed = now.saturating_sub(last_now);
+                    let latency = elapsed.saturating_sub(idle.saturating_sub(last_idle));
+                    samples.iteration_latency = latency;
+                    samples.iteration_latency_max = samples.iteration_latency_max.max(latency);
+                }
+                samples.last_check = Some((now, idle));
+            })
+        });
+        if let Err(e) = result {
+            prepare.close(());
+            check.close(());
+            return Err(e);
+        }
+
+        prepare.unref();
+        check.unref();
+
+        Ok(MetricsCollector {
+            prepare,
+            check,
+            samples,
+        })
+    }
+
+    /// Take a snapshot of the loop's metrics.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let r#loop = self.prepare.get_loop();
+        let samples = self.samples.borrow();
+        let metrics = samples
+            .metrics
+            .as_ref()
+            .map(|m| (m.loop_count, m.events, m.events_waiting))
+            .or_else(|| {
+                r#loop
+                    .metrics_info()
+                    .ok()
+                    .map(|m| (m.loop_count, m.events, m.events_waiting))
+            })
+            .unwrap_or_default();
+
+        let counts = Rc::new(RefCell::new(HandleCounts::default()));
+        let walk_counts = counts.clone();
+        let own: [*mut uv_handle_t; 2] = [
+            self.prepare.to_handle().inner(),
+            self.check.to_handle().inner(),
+        ];
+        r#loop.walk(move |handle: crate::Handle| {
+            let ptr: *mut uv_handle_t = handle.inner();
+            if handle.is_closing() || own.contains(&ptr) {
+                return;
+            }
+
+            let mut counts = walk_counts.borrow_mut();
+            *counts.handles.entry(handle.get_type()).or_insert(0) += 1;
+            if let Ok(timer) = crate::TimerHandle::try_from(handle) {
+                if timer.is_active() {
+                    counts.timers += 1;
+                    if timer.due_in() == 0 {
+                        counts.timers_overdue += 1;
+                    }
+                }
+            }
+        });
+        let counts = counts.replace(Default::default());
+
+        MetricsSnapshot {
+            loop_count: metrics.0,
+            events: metrics.1,
+            events_waiting: metrics.2,
+            idle_time: r#loop.idle_time(),
+            handles: counts.handles,
+            requests: r#loop.pending_reqs(),
+            timers: counts.timers,
+            timers_overdue: counts.timers_overdue,
+            iteration_latency: samples.iteration_latency,
+            iteration_latency_max: samples.iteration_latency_max,
+        }
+    }
+
+    /// Stop collecting metrics and close the underlying handles, unless they are already closing
+    /// (after Loop::close_all(), for example).
+    pub fn close(&mut self) {
+        if !self.prepare.is_closing() {
+            self.prepare.close(());
+        }
+        if !self.check.is_closing() {
+            self.check.close(());
+        }
+    }
+}
+
+/// Handle counts gathered by walking the loop.
+#[derive(Default)]
+struct HandleCounts {
+    handles: HashMap<HandleType, usize>,
+    timers: usize,
+    timers_overdue: usize,
+}
+
+impl crate::Loop {
+    /// Create a new MetricsCollector for this loop. This will configure the loop to
+    /// accumulate_idle_time().
+    pub fn metrics_collector(&mut self) -> crate::Result<MetricsCollector> {
+        MetricsCollector::new(self)
+    }
+}
@@ -1,19 +1,256 @@
 use crate::{FromInner, IntoInner};
 use std::net::SocketAddr;
-use uv::addrinfo;
+use uv::{
+    addrinfo, AF_INET, AF_INET6, AF_UNSPEC, AI_ADDRCONFIG, AI_ALL, AI_CANONNAME, AI_NUMERICHOST,
+    AI_NUMERICSERV, AI_PASSIVE, AI_V4MAPPED, IPPROTO_ICMP, IPPROTO_IP, IPPROTO_IPV6, IPPROTO_TCP,
+    IPPROTO_UDP, SOCK_DGRAM, SOCK_RAW, SOCK_SEQPACKET, SOCK_STREAM,
+};
 
+bitflags! {
+    /// Flags for getaddrinfo() hints and results. Consult man -s 3 getaddrinfo for more details.
+    pub struct AiFlags: u32 {
+        /// The returned addresses will be suitable for bind()ing a socket that will accept()
+        /// connections. Only applies if node is None.
+        const PASSIVE = AI_PASSIVE as _;
+
+        /// Return the official name of the host in the canonical_name of the first AddrInfo.
+        const CANONNAME = AI_CANONNAME as _;
+
+        /// node must be a numerical network address; no name resolution is performed.
+        const NUMERICHOST = AI_NUMERICHOST as _;
+
+        /// service must be a numerical port number; no service name resolution is performed.
+        const NUMERICSERV = AI_NUMERICSERV as _;
+
+        /// If the family is AddressFamily::Inet6 and no IPv6 addresses are found, return
+        /// IPv4-mapped IPv6 addresses instead.
+        const V4MAPPED = AI_V4MAPPED as _;
+
+        /// Combined with V4MAPPED, return both IPv6 and IPv4-mapped IPv6 addresses.
+        const ALL = AI_ALL as _;
+
+        /// Only return IPv4 addresses if the local system has an IPv4 address configured, and only
+        /// return IPv6 addresses if the local system has an IPv6 address configured.
+        const ADDRCONFIG = AI_ADDRCONFIG as _;
+    }
+}
+
+impl Default for AiFlags {
+    fn default() -> Self {
+        AiFlags::empty()
+    }
+}
+
+/// Address family of a socket address.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum AddressFamily {
+    /// Any address family (AF_UNSPEC).
+    #[default]
+    Unspec,
+
+    /// IPv4 (AF_INET).
+    Inet,
+
+    /// IPv6 (AF_INET6).
+    Inet6,
+
+    /// Any other address family.
+    Other(i32),
+}
+
+impl FromInner<i32> for AddressFamily {
+    fn from_inner(family: i32) -> AddressFamily {
+        match family as u32 {
+            AF_UNSPEC => AddressFamily::Unspec,
+            AF_INET => AddressFamily::Inet,
+            AF_INET6 => AddressFamily::Inet6,
+            _ => AddressFamily::Other(family),
+        }
+    }
+}
+
+impl IntoInner<i32> for AddressFamily {
+    fn into_inner(self) -> i32 {
+        match self {
+            AddressFamily::Unspec => AF_UNSPEC as _,
+            AddressFamily::Inet => AF_INET as _,
+            AddressFamily::Inet6 => AF_INET6 as _,
+            AddressFamily::Other(family) => family,
+        }
+    }
+}
+
+/// Socket type.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SocketType {
+    /// Any socket type.
+    #[default]
+    Any,
+
+    /// Sequenced, reliable, connection-based byte streams (SOCK_STREAM).
+    Stream,
+
+    /// Connectionless, unreliable datagrams (SOCK_DGRAM).
+    Datagram,
+
+    /// Raw network protocol access (SOCK_RAW).
+    Raw,
+
+    /// Sequenced, reliable, connection-based datagrams (SOCK_SEQPACKET).
+    SeqPacket,
+
+    /// Any other socket type.
+    Other(i32),
+}
+
+impl FromInner<i32> for SocketType {
+    fn from_inner(socktype: i32) -> SocketType {
+        match socktype as _ {
+            0 => SocketType::Any,
+            SOCK_STREAM => SocketType::Stream,
+            SOCK_DGRAM => SocketType::Datagram,
+            SOCK_RAW => SocketType::Raw,
+            SOCK_SEQPACKET => SocketType::SeqPacket,
+            _ => SocketType::Other(socktype),
+        }
+    }
+}
+
+impl IntoInner<i32> for SocketType {
+    fn into_inner(self) -> i32 {
+        match self {
+            SocketType::Any => 0,
+            SocketType::Stream => SOCK_STREAM as _,
+            SocketType::Datagram => SOCK_DGRAM as _,
+            SocketType::Raw => SOCK_RAW as _,
+            SocketType::SeqPacket => SOCK_SEQPACKET as _,
+            SocketType::Other(socktype) => socktype,
+        }
+    }
+}
+
+/// Socket protocol.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Protocol {
+    /// Any protocol (IPPROTO_IP).
+    #[default]
+    Any,
+
+    /// IPPROTO_TCP
+    Tcp,
+
+    /// IPPROTO_UDP
+    Udp,
+
+    /// IPPROTO_ICMP
+    Icmp,
+
+    /// IPPROTO_IPV6
+    Ipv6,
+
+    /// Any other protocol.
+    Other(i32),
+}
+
+impl FromInner<i32> for Protocol {
+    fn from_inner(protocol: i32) -> Protocol {
+        match protocol as _ {
+            IPPROTO_IP => Protocol::Any,
+            IPPROTO_TCP => Protocol::Tcp,
+            IPPROTO_UDP => Protocol::Udp,
+            IPPROTO_ICMP => Protocol::Icmp,
+            IPPROTO_IPV6 => Protocol::Ipv6,
+            _ => Protocol::Other(protocol),
+        }
+    }
+}
+
+impl IntoInner<i32> for Protocol {
+    fn into_inner(self) -> i32 {
+        match self {
+            Protocol::Any => IPPROTO_IP as _,
+            Protocol::Tcp => IPPROTO_TCP as _,
+            Protocol::Udp => IPPROTO_UDP as _,
+            Protocol::Icmp => IPPROTO_ICMP as _,
+            Protocol::Ipv6 => IPPROTO_IPV6 as _,
+            Protocol::Other(protocol) => protocol,
+        }
+    }
+}
+
+/// Address type constraints for getaddrinfo(). By default, addresses of any family, socket type
+/// and protocol are returned.
+///
+/// ```
+/// # use libuv::{AddressFamily, AiFlags, Hints, SocketType};
+/// let hints = Hints::new()
+///     .family(AddressFamily::Inet)
+///     .socktype(SocketType::Stream)
+///     .flags(AiFlags::ADDRCONFIG);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Hints {
+    flags: AiFlags,
+    family: AddressFamily,
+    socktype: SocketType,
+    protocol: Protocol,
+}
+
+impl Hints {
+    /// Create hints that do not constrain the results.
+    pub fn new() -> Hints {
+        Default::default()
+    }
+
+    /// Set the AiFlags.
+    pub fn flags(mut self, flags: AiFlags) -> Hints {
+        self.flags = flags;
+        self
+    }
+
+    /// Only return addresses of the given family.
+    pub fn family(mut self, family: AddressFamily) -> Hints {
+        self.family = family;
+        self
+    }
+
+    /// Only return addresses for the given socket type.
+    pub fn socktype(mut self, socktype: SocketType) -> Hints {
+        self.socktype = socktype;
+        self
+    }
+
+    /// Only return addresses for the given protocol.
+    pub fn protocol(mut self, protocol: Protocol) -> Hints {
+        self.protocol = protocol;
+        self
+    }
+}
+
+impl IntoInner<addrinfo> for Hints {
+    fn into_inner(self) -> addrinfo {
+        let mut ai: addrinfo = unsafe { std::mem::zeroed() };
+        ai.ai_flags = self.flags.bits() as _;
+        ai.ai_family = self.family.into_inner();
+        ai.ai_socktype = self.socktype.into_inner();
+        ai.ai_protocol = self.protocol.into_inner();
+        ai
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct AddrInfo {
-    /// Bitwise OR of uv::AI_* flags
-    pub flags: u32,
+    /// Flags
+    pub flags: AiFlags,
 
-    /// One of the uv::AF_* constants
-    pub family: u32,
+    /// The address family
+    pub family: AddressFamily,
 
-    /// One of the uv::SOCK_* constants
-    pub socktype: u32,
+    /// The socket type
+    pub socktype: SocketType,
 
-    /// One of the uv::IPPROTO_* constants
-    pub protocol: u32,
+    /// The protocol
+    pub protocol: Protocol,
 
     /// The canonical name of the host
     pub canonical_name: Option<String>,
@@ -40,10 +277,10 @@ impl FromInner<*mut addrinfo> for AddrInfo {
                 crate::build_socketaddr((*info).ai_addr).ok()
             };
             AddrInfo {
-                flags: (*info).ai_flags as _,
-                family: (*info).ai_family as _,
-                socktype: (*info).ai_socktype as _,
-                protocol: (*info).ai_protocol as _,
+                flags: AiFlags::from_bits_truncate((*info).ai_flags as _),
+                family: (*info).ai_family.into_inner(),
+                socktype: (*info).ai_socktype.into_inner(),
+                protocol: (*info).ai_protocol.into_inner(),
                 canonical_name,
                 addr,
             }
@@ -51,17 +288,6 @@ impl FromInner<*mut addrinfo> for AddrInfo {
     }
 }
 
-impl IntoInner<addrinfo> for AddrInfo {
-    fn into_inner(self) -> addrinfo {
-        let mut ai: addrinfo = unsafe { std::mem::zeroed() };
-        ai.ai_flags = self.flags as _;
-        ai.ai_family = self.family as _;
-        ai.ai_socktype = self.socktype as _;
-        ai.ai_protocol = self.protocol as _;
-        ai
-    }
-}
-
 impl FromInner<*mut addrinfo> for Vec<AddrInfo> {
     fn from_inner(info: *mut addrinfo) -> Vec<AddrInfo> {
         let mut v = Vec::new();
@@ -0,0 +1,230 @@
+use crate::{AiFlags, HandleTrait, Hints, Inner, Protocol, SocketType};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use uv::uv_tcp_t;
+
+/// How long to wait for a connection attempt before starting the next one, as recommended by RFC
+/// 8305.
+const CONNECTION_ATTEMPT_DELAY: u64 = 250;
+
+callbacks! {
+    pub TcpConnectHostCB(result: crate::Result<crate::TcpHandle>);
+}
+
+/// The state of a tcp_connect_host() call, shared by the callbacks of all connection attempts.
+struct HappyEyeballs {
+    cb: TcpConnectHostCB<'static>,
+    addrs: VecDeque<SocketAddr>,
+    attempts: Vec<crate::TcpHandle>,
+    timer: Option<crate::TimerHandle>,
+    error: Option<crate::Error>,
+    done: bool,
+}
+
+type State = Rc<RefCell<HappyEyeballs>>;
+
+/// Sort the addresses so that address families alternate, starting with the family of the first
+/// address (RFC 8305, section 4).
+fn interleave(addrs: Vec<SocketAddr>) -> VecDeque<SocketAddr> {
+    let prefer_v6 = !matches!(addrs.first(), Some(SocketAddr::V4(_)));
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == prefer_v6);
+
+    let mut result = VecDeque::with_capacity(first.len() + second.len());
+    loop {
+        match (first.pop_front(), second.pop_front()) {
+            (None, None) => break,
+            (a, b) => result.extend(a.into_iter().chain(b)),
+        }
+    }
+    result
+}
+
+/// Start a connection attempt to the next address. If there are more addresses after this one, a
+/// timer is armed to start the next attempt if this one hasn't finished in time.
+fn start_next(r#loop: &crate::Loop, state: &State) {
+    loop {
+        let next = state.borrow_mut().addrs.pop_front();
+        let addr = match next {
+            Some(addr) => addr,
+            None => {
+                finish_if_failed(state);
+                return;
+            }
+        };
+
+        let attempt_state = state.clone();
+        let result = crate::TcpHandle::new(r#loop)
+            .map_err(|e| Box::new(e) as _)
+            .and_then(|mut tcp| {
+                let result = tcp.connect(&addr, move |req: crate::ConnectReq, status| {
+                    on_connect(&attempt_state, req, status)
+                });
+                if result.is_err() {
+                    tcp.close(());
+                }
+                result.map(|_| tcp)
+            });
+
+        match result {
+            Ok(tcp) => {
+                state.borrow_mut().attempts.push(tcp);
+                arm_timer(r#loop, state);
+                return;
+            }
+            Err(e) => {
+                // the attempt could not be started - move on to the next address right away
+                if let Some(e) = e.downcast_ref::<crate::Error>() {
+                    state.borrow_mut().error = Some(*e);
+                }
+            }
+        }
+    }
+}
+
+/// Arm the timer that starts the next connection attempt, if there are any addresses left.
+fn arm_timer(r#loop: &crate::Loop, state: &State) {
+    let mut s = state.borrow_mut();
+    if s.addrs.is_empty() {
+        if let Some(mut timer) = s.timer.take() {
+            timer.close(());
+        }
+        return;
+    }
+
+    if s.timer.is_none() {
+        s.timer = r#loop.timer().ok();
+    }
+    if let Some(mut timer) = s.timer {
+        let timer_state = state.clone();
+        let result = timer.start(
+            CONNECTION_ATTEMPT_DELAY,
+            0,
+            move |timer: crate::TimerHandle| start_next(&timer.get_loop(), &timer_state),
+        );
+        if result.is_err() {
+            timer.close(());
+            s.timer = None;
+        }
+    }
+}
+
+/// Called when a connection attempt finishes.
+fn on_connect(state: &State, req: crate::ConnectReq, status: crate::Result<u32>) {
+    let mut tcp = match crate::TcpHandle::try_from(req.handle()) {
+        Ok(tcp) => tcp,
+        Err(_) => return,
+    };
+
+    let mut s = state.borrow_mut();
+    let ptr: *mut uv_tcp_t = tcp.inner();
+    s.attempts
+        .retain(|attempt| Inner::<*mut uv_tcp_t>::inner(attempt) != ptr);
+    if s.done {
+        // we already have a winner
+        if !tcp.is_closing() {
+            tcp.close(());
+        }
+        return;
+    }
+
+    match status {
+        Ok(_) => {
+            s.done = true;
+            for mut loser in s.attempts.drain(..) {
+                if !loser.is_closing() {
+                    loser.close(());
+                }
+            }
+            if let Some(mut timer) = s.timer.take() {
+                timer.close(());
+            }
+            let mut cb = std::mem::take(&mut s.cb);
+            drop(s);
+            cb.call(Ok(tcp));
+        }
+        Err(e) => {
+            if !tcp.is_closing() {
+                tcp.close(());
+            }
+            s.error = Some(e);
+            drop(s);
+
+            // don't wait for the timer - start the next attempt now
+            start_next(&tcp.get_loop(), state);
+        }
+    }
+}
+
+/// Call the callback with the last error if every connection attempt has failed.
+fn finish_if_failed(state: &State) {
+    let mut s = state.borrow_mut();
+    if s.done || !s.attempts.is_empty() || !s.addrs.is_empty() {
+        return;
+    }
+
+    s.done = true;
+    if let Some(mut timer) = s.timer.take() {
+        timer.close(());
+    }
+    // no error means getaddrinfo() did not return any addresses
+    let error = s.error.take().unwrap_or(crate::Error::EAI_NONAME);
+    let mut cb = std::mem::take(&mut s.cb);
+    drop(s);
+    cb.call(Err(error));
+}
+
+impl crate::Loop {
+    /// Resolve host and connect to it using the "Happy Eyeballs" algorithm from RFC 8305.
+    ///
+    /// The host is resolved with getaddrinfo(), and the resulting addresses are sorted so that
+    /// IPv6 and IPv4 addresses alternate. Connection attempts are started one after another, 250ms
+    /// apart, or sooner if the previous attempt fails. The first connection to succeed is passed to
+    /// the callback and all other attempts are closed.
+    ///
+    /// If the host cannot be resolved, or none of the connection attempts succeed, the callback
+    /// receives the last error.
+    pub fn tcp_connect_host<CB: Into<TcpConnectHostCB<'static>>>(
+        &self,
+        host: &str,
+        port: u16,
+        cb: CB,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let state = Rc::new(RefCell::new(HappyEyeballs {
+            cb: cb.into(),
+            addrs: VecDeque::new(),
+            attempts: Vec::new(),
+            timer: None,
+            error: None,
+            done: false,
+        }));
+
+        let hints = Hints::new()
+            .flags(AiFlags::NUMERICSERV)
+            .socktype(SocketType::Stream)
+            .protocol(Protocol::Tcp);
+        self.getaddrinfo(
+            Some(host),
+            Some(&port.to_string()),
+            Some(hints),
+            move |req: crate::GetAddrInfoReq,
+                  status: crate::Result<u32>,
+                  res: Vec<crate::AddrInfo>| {
+                if let Err(e) = status {
+                    state.borrow_mut().error = Some(e);
+                    finish_if_failed(&state);
+                    return;
+                }
+
+                let addrs = res.into_iter().filter_map(|ai| ai.addr).collect();
+                state.borrow_mut().addrs = interleave(addrs);
+                start_next(&req.r#loop(), &state);
+            },
+        )?;
+        Ok(())
+    }
+}
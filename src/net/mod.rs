@@ -3,3 +3,25 @@ pub use addrinfo::*;
 
 pub mod misc;
 pub use misc::*;
+
+pub mod happy_eyeballs;
+pub use happy_eyeballs::*;
+
+pub mod resolver;
+pub use resolver::*;
+
+pub mod reverse_lookup;
+pub use reverse_lookup::*;
+
+pub mod tcp_server;
+pub use tcp_server::*;
+
+#[cfg(unix)]
+pub mod std_net;
+#[cfg(unix)]
+pub use std_net::*;
+
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(unix)]
+pub use systemd::*;
@@ -0,0 +1,205 @@
+use crate::{AddrInfo, Hints};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+callbacks! {
+    pub ResolveCB(result: crate::Result<Vec<AddrInfo>>);
+}
+
+/// Cache key: the arguments to getaddrinfo().
+type Key = (Option<String>, Option<String>, Option<Hints>);
+
+/// A cached result, along with the loop time (in ms) at which it expires.
+struct Entry {
+    result: crate::Result<Vec<AddrInfo>>,
+    expires: u64,
+}
+
+struct ResolverState {
+    r#loop: crate::Loop,
+    ttl: u64,
+    negative_ttl: u64,
+    max_concurrent: usize,
+    cache: HashMap<Key, Entry>,
+    pending: HashMap<Key, Vec<ResolveCB<'static>>>,
+    queue: VecDeque<Key>,
+    active: usize,
+}
+
+/// A caching, asynchronous DNS resolver built on Loop::getaddrinfo().
+///
+/// Successful results are cached for ttl milliseconds; lookups that fail with EAI_NONAME are
+/// cached for negative_ttl milliseconds. Other errors are not cached. Concurrent lookups for the
+/// same host, service and hints share a single getaddrinfo request.
+///
+/// getaddrinfo requests run on the libuv threadpool. To leave room in the threadpool for other
+/// work, such as file system requests, the resolver runs at most max_concurrent requests at a time
+/// and queues the rest.
+///
+/// Resolvers can be cloned cheaply; clones share the same cache.
+#[derive(Clone)]
+pub struct Resolver {
+    state: Rc<RefCell<ResolverState>>,
+}
+
+impl Resolver {
+    /// Create a new Resolver with a ttl of 60 seconds, a negative_ttl of 5 seconds and at most 2
+    /// concurrent requests.
+    pub fn new(r#loop: &crate::Loop) -> Resolver {
+        Resolver {
+            state: Rc::new(RefCell::new(ResolverState {
+                r#loop: r#loop.clone(),
+                ttl: 60_000,
+                negative_ttl: 5_000,
+                max_concurrent: 2,
+                cache: HashMap::new(),
+                pending: HashMap::new(),
+                queue: VecDeque::new(),
+                active: 0,
+            })),
+        }
+    }
+
+    /// Set how long, in milliseconds, successful lookups are cached. A ttl of 0 disables caching,
+    /// though concurrent lookups are still coalesced.
+    pub fn ttl(self, ttl: u64) -> Resolver {
+        self.state.borrow_mut().ttl = ttl;
+        self
+    }
+
+    /// Set how long, in milliseconds, lookups that fail with EAI_NONAME are cached. A negative_ttl
+    /// of 0 disables negative caching.
+    pub fn negative_ttl(self, negative_ttl: u64) -> Resolver {
+        self.state.borrow_mut().negative_ttl = negative_ttl;
+        self
+    }
+
+    /// Set the maximum number of getaddrinfo requests that may run on the threadpool at the same
+    /// time. Must be at least 1.
+    pub fn max_concurrent(self, max_concurrent: usize) -> Resolver {
+        self.state.borrow_mut().max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    /// Resolve a node and/or service, like Loop::getaddrinfo().
+    ///
+    /// If a result for the same node, service and hints is cached, the callback is called before
+    /// resolve() returns. Otherwise, it is called once the lookup completes.
+    pub fn resolve<CB: Into<ResolveCB<'static>>>(
+        &self,
+        node: Option<&str>,
+        service: Option<&str>,
+        hints: Option<Hints>,
+        cb: CB,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if node.is_none() && service.is_none() {
+            return Err(Box::new(crate::Error::EINVAL));
+        }
+
+        let key: Key = (node.map(str::to_owned), service.map(str::to_owned), hints);
+        let mut cb = cb.into();
+        let mut state = self.state.borrow_mut();
+        let now = state.r#loop.now();
+        if let Some(entry) = state.cache.get(&key) {
+            if entry.expires > now {
+                let result = entry.result.clone();
+                drop(state);
+                cb.call(result);
+                return Ok(());
+            }
+            state.cache.remove(&key);
+        }
+
+        if let Some(waiting) = state.pending.get_mut(&key) {
+            // a lookup is already running or queued
+            waiting.push(cb);
+            return Ok(());
+        }
+
+        state.pending.insert(key.clone(), vec![cb]);
+        if state.active < state.max_concurrent {
+            drop(state);
+            self.start(key);
+        } else {
+            state.queue.push_back(key);
+        }
+        Ok(())
+    }
+
+    /// Remove all cached results.
+    pub fn clear(&self) {
+        self.state.borrow_mut().cache.clear();
+    }
+
+    /// Start a getaddrinfo request for the given key.
+    fn start(&self, key: Key) {
+        let r#loop = {
+            let mut state = self.state.borrow_mut();
+            state.active += 1;
+            state.r#loop.clone()
+        };
+
+        let resolver = self.clone();
+        let (node, service, hints) = key.clone();
+        let cb_key = key.clone();
+        let result = r#loop.getaddrinfo(
+            node.as_deref(),
+            service.as_deref(),
+            hints,
+            move |_, status: crate::Result<u32>, res: Vec<AddrInfo>| {
+                resolver.finish(cb_key.clone(), status.map(|_| res));
+            },
+        );
+        if let Err(e) = result {
+            let e = match e.downcast_ref::<crate::Error>() {
+                Some(e) => *e,
+                None => crate::Error::EINVAL,
+            };
+            self.finish(key, Err(e));
+        }
+    }
+
+    /// Cache the result of a lookup, pass it to everyone that was waiting for it, and start the
+    /// next queued lookup.
+    fn finish(&self, key: Key, result: crate::Result<Vec<AddrInfo>>) {
+        let (waiting, next) = {
+            let mut state = self.state.borrow_mut();
+            state.active -= 1;
+
+            let ttl = match result {
+                Ok(_) => state.ttl,
+                Err(crate::Error::EAI_NONAME) => state.negative_ttl,
+                Err(_) => 0,
+            };
+            if ttl > 0 {
+                let now = state.r#loop.now();
+                state.cache.retain(|_, entry| entry.expires > now);
+                state.cache.insert(
+                    key.clone(),
+                    Entry {
+                        result: result.clone(),
+                        expires: now.saturating_add(ttl),
+                    },
+                );
+            }
+
+            let waiting = state.pending.remove(&key).unwrap_or_default();
+            (waiting, state.queue.pop_front())
+        };
+
+        if let Some(next) = next {
+            self.start(next);
+        }
+        for mut cb in waiting {
+            cb.call(result.clone());
+        }
+    }
+}
+
+impl crate::Loop {
+    /// Create a new caching Resolver for this loop. See Resolver::new().
+    pub fn resolver(&self) -> Resolver {
+        Resolver::new(self)
+    }
+}
@@ -0,0 +1,112 @@
+use crate::NameInfoFlags;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+/// The results of a reverse_lookup(): a (host, service) tuple or an error for each address.
+pub type ReverseLookupResults = HashMap<SocketAddr, crate::Result<(String, String)>>;
+
+callbacks! {
+    pub ReverseLookupCB(results: ReverseLookupResults);
+}
+
+/// The state of a reverse_lookup() call.
+struct ReverseLookup {
+    flags: NameInfoFlags,
+    cb: ReverseLookupCB<'static>,
+    addrs: VecDeque<SocketAddr>,
+    results: ReverseLookupResults,
+    active: usize,
+}
+
+type State = Rc<RefCell<ReverseLookup>>;
+
+/// Start getnameinfo requests until max_concurrent are running or there are no addresses left.
+/// Once all requests have completed, the callback is called with the results.
+fn start_lookups(r#loop: &crate::Loop, state: &State, max_concurrent: usize) {
+    loop {
+        let next = {
+            let mut s = state.borrow_mut();
+            if s.active >= max_concurrent {
+                return;
+            }
+            s.addrs.pop_front().map(|addr| (addr, s.flags))
+        };
+
+        let (addr, flags) = match next {
+            Some(next) => next,
+            None => break,
+        };
+
+        let lookup_state = state.clone();
+        let result = r#loop.getnameinfo(
+            &addr,
+            flags,
+            move |req: crate::GetNameInfoReq,
+                  status: crate::Result<u32>,
+                  host: String,
+                  service: String| {
+                {
+                    let mut s = lookup_state.borrow_mut();
+                    s.active -= 1;
+                    s.results.insert(addr, status.map(|_| (host, service)));
+                }
+                start_lookups(&req.r#loop(), &lookup_state, max_concurrent);
+            },
+        );
+
+        let mut s = state.borrow_mut();
+        match result {
+            Ok(_) => s.active += 1,
+            Err(e) => {
+                let e = match e.downcast_ref::<crate::Error>() {
+                    Some(e) => *e,
+                    None => crate::Error::EINVAL,
+                };
+                s.results.insert(addr, Err(e));
+            }
+        }
+    }
+
+    let mut s = state.borrow_mut();
+    if s.active == 0 && !s.cb.is_nil() {
+        let mut cb = std::mem::take(&mut s.cb);
+        let results = std::mem::take(&mut s.results);
+        drop(s);
+        cb.call(results);
+    }
+}
+
+impl crate::Loop {
+    /// Look up the host and service names of many addresses using getnameinfo(3). At most
+    /// max_concurrent lookups run on the threadpool at the same time.
+    ///
+    /// Once every lookup has completed, the callback is called with a map from each address to its
+    /// (host, service) tuple, or the error that its lookup failed with. Duplicate addresses are
+    /// only looked up once. If addrs is empty, the callback is called before reverse_lookup()
+    /// returns.
+    pub fn reverse_lookup<CB: Into<ReverseLookupCB<'static>>>(
+        &self,
+        addrs: &[SocketAddr],
+        flags: NameInfoFlags,
+        max_concurrent: usize,
+        cb: CB,
+    ) {
+        let mut seen = HashSet::with_capacity(addrs.len());
+        let unique = addrs
+            .iter()
+            .filter(|addr| seen.insert(**addr))
+            .copied()
+            .collect();
+
+        let state = Rc::new(RefCell::new(ReverseLookup {
+            flags,
+            cb: cb.into(),
+            addrs: unique,
+            results: HashMap::with_capacity(addrs.len()),
+            active: 0,
+        }));
+        start_lookups(self, &state, max_concurrent.max(1));
+    }
+}
@@ -0,0 +1,158 @@
+//! Conversions between handles and the std socket types. from_std() hands a std socket over to a
+//! new handle, and to_std() and friends duplicate the socket of a handle into a std socket.
+//!
+//! Note: a duplicate shares the non-blocking mode of the handle's socket (it is a property of the
+//! open file description, not of the descriptor), and libuv relies on that mode. Do not change it
+//! with set_nonblocking(false): reads and writes on the handle would start to block the loop.
+
+use crate::{FromInner, HandleTrait};
+use std::mem::ManuallyDrop;
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+/// std::net sockets that can be turned into a TcpHandle with TcpHandle::from_std().
+pub trait StdTcpSocket: AsRawFd + IntoRawFd {}
+
+impl StdTcpSocket for TcpStream {}
+impl StdTcpSocket for TcpListener {}
+
+/// std::os::unix::net sockets that can be turned into a PipeHandle with PipeHandle::from_std().
+pub trait StdUnixSocket: AsRawFd + IntoRawFd {}
+
+impl StdUnixSocket for UnixStream {}
+impl StdUnixSocket for UnixListener {}
+
+/// Convert an io::Error from std into the equivalent libuv error.
+fn from_io_error(e: std::io::Error) -> crate::Error {
+    match e.raw_os_error() {
+        Some(errno) => {
+            let code = unsafe { uv::uv_translate_sys_error(errno) };
+            crate::Error::from_inner(code as uv::uv_errno_t)
+        }
+        None => crate::Error::UNKNOWN,
+    }
+}
+
+/// Duplicate the descriptor of a handle into a std socket. The handle keeps its own descriptor.
+fn duplicate<T: FromRawFd>(
+    handle: &impl HandleTrait,
+    try_clone: fn(&T) -> std::io::Result<T>,
+) -> crate::Result<T> {
+    let fd = handle.get_fileno()?;
+
+    // the handle still owns fd, so the temporary socket must not close it
+    let socket = ManuallyDrop::new(unsafe { T::from_raw_fd(fd) });
+    try_clone(&socket).map_err(from_io_error)
+}
+
+impl crate::TcpHandle {
+    /// Create a TCP handle from a std TcpStream or TcpListener. The handle takes ownership of the
+    /// socket, which is set to non-blocking mode.
+    pub fn from_std(
+        r#loop: &crate::Loop,
+        socket: impl StdTcpSocket,
+    ) -> crate::Result<crate::TcpHandle> {
+        let mut handle = crate::TcpHandle::new(r#loop)?;
+        if let Err(e) = handle.open(socket.as_raw_fd()) {
+            handle.close(());
+            return Err(e);
+        }
+        // the handle now owns the descriptor
+        let _ = socket.into_raw_fd();
+        Ok(handle)
+    }
+
+    /// Duplicate the socket of a connected TCP handle into a std TcpStream.
+    ///
+    /// See the std_net module documentation about non-blocking mode.
+    pub fn to_std_stream(&self) -> crate::Result<TcpStream> {
+        duplicate(self, TcpStream::try_clone)
+    }
+
+    /// Duplicate the socket of a listening TCP handle into a std TcpListener.
+    ///
+    /// See the std_net module documentation about non-blocking mode.
+    pub fn to_std_listener(&self) -> crate::Result<TcpListener> {
+        duplicate(self, TcpListener::try_clone)
+    }
+}
+
+impl crate::UdpHandle {
+    /// Create a UDP handle from a std UdpSocket. The handle takes ownership of the socket, which is
+    /// set to non-blocking mode.
+    pub fn from_std(r#loop: &crate::Loop, socket: UdpSocket) -> crate::Result<crate::UdpHandle> {
+        let mut handle = crate::UdpHandle::new(r#loop)?;
+        if let Err(e) = handle.open(socket.as_raw_fd()) {
+            handle.close(());
+            return Err(e);
+        }
+        // the handle now owns the descriptor
+        let _ = socket.into_raw_fd();
+        Ok(handle)
+    }
+
+    /// Duplicate the socket of a UDP handle into a std UdpSocket.
+    ///
+    /// See the std_net module documentation about non-blocking mode.
+    pub fn to_std(&self) -> crate::Result<UdpSocket> {
+        duplicate(self, UdpSocket::try_clone)
+    }
+}
+
+impl crate::PipeHandle {
+    /// Create a pipe handle from a std UnixStream or UnixListener. The handle takes ownership of
+    /// the socket, which is set to non-blocking mode.
+    pub fn from_std(
+        r#loop: &crate::Loop,
+        socket: impl StdUnixSocket,
+        ipc: bool,
+    ) -> crate::Result<crate::PipeHandle> {
+        let mut handle = crate::PipeHandle::new(r#loop, ipc)?;
+        if let Err(e) = handle.open(socket.as_raw_fd()) {
+            handle.close(());
+            return Err(e);
+        }
+        // the handle now owns the descriptor
+        let _ = socket.into_raw_fd();
+        Ok(handle)
+    }
+
+    /// Duplicate the socket of a connected pipe handle into a std UnixStream.
+    ///
+    /// See the std_net module documentation about non-blocking mode.
+    pub fn to_std_stream(&self) -> crate::Result<UnixStream> {
+        duplicate(self, UnixStream::try_clone)
+    }
+
+    /// Duplicate the socket of a listening pipe handle into a std UnixListener.
+    ///
+    /// See the std_net module documentation about non-blocking mode.
+    pub fn to_std_listener(&self) -> crate::Result<UnixListener> {
+        duplicate(self, UnixListener::try_clone)
+    }
+}
+
+/// Implement AsRawFd using get_fileno(). as_raw_fd() returns -1 if the handle does not have a
+/// file descriptor, for example if it has been closed.
+macro_rules! impl_as_raw_fd {
+    ($($t:ty),*) => {
+        $(
+            impl AsRawFd for $t {
+                fn as_raw_fd(&self) -> RawFd {
+                    self.get_fileno().unwrap_or(-1)
+                }
+            }
+        )*
+    };
+}
+
+impl_as_raw_fd!(
+    crate::Handle,
+    crate::StreamHandle,
+    crate::TcpHandle,
+    crate::UdpHandle,
+    crate::PipeHandle,
+    crate::TtyHandle,
+    crate::PollHandle
+);
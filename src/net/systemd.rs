@@ -0,0 +1,156 @@
+use crate::HandleTrait;
+use std::net::SocketAddr;
+
+/// The first file descriptor passed by systemd socket activation.
+const LISTEN_FDS_START: crate::File = 3;
+
+/// A handle opened on a file descriptor that was passed by systemd socket activation.
+pub enum ActivatedSocket {
+    Tcp(crate::TcpHandle),
+    Udp(crate::UdpHandle),
+    Pipe(crate::PipeHandle),
+
+    /// A descriptor that libuv cannot open as a TCP, UDP or pipe handle, such as a unix datagram
+    /// socket or a character device. The descriptor is left as it is.
+    Other(crate::File),
+}
+
+/// A file descriptor that was passed by systemd socket activation.
+pub struct ListenFd {
+    /// The file descriptor.
+    pub fd: crate::File,
+
+    /// The name of the descriptor from FileDescriptorName= in the socket unit, or "unknown" if it
+    /// was not named.
+    pub name: String,
+
+    /// The address the socket is bound to, for TCP and UDP sockets.
+    pub local_addr: Option<SocketAddr>,
+
+    /// The handle opened on the descriptor.
+    pub socket: ActivatedSocket,
+}
+
+impl ActivatedSocket {
+    /// Open a handle on an inherited descriptor, based on what guess_handle() says it is.
+    fn open(r#loop: &crate::Loop, fd: crate::File) -> crate::Result<ActivatedSocket> {
+        match crate::guess_handle(fd) {
+            crate::HandleType::TCP => {
+                let mut handle = r#loop.tcp()?;
+                if let Err(e) = handle.open(fd as _) {
+                    handle.close(());
+                    return Err(e);
+                }
+                Ok(ActivatedSocket::Tcp(handle))
+            }
+            crate::HandleType::UDP => {
+                let mut handle = r#loop.udp()?;
+                if let Err(e) = handle.open(fd as _) {
+                    handle.close(());
+                    return Err(e);
+                }
+                Ok(ActivatedSocket::Udp(handle))
+            }
+            crate::HandleType::NAMED_PIPE => {
+                let mut handle = r#loop.pipe(false)?;
+                if let Err(e) = handle.open(fd) {
+                    handle.close(());
+                    return Err(e);
+                }
+                Ok(ActivatedSocket::Pipe(handle))
+            }
+            _ => Ok(ActivatedSocket::Other(fd)),
+        }
+    }
+
+    /// The address that a TCP or UDP socket is bound to.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            ActivatedSocket::Tcp(handle) => handle.getsockname().ok(),
+            ActivatedSocket::Udp(handle) => handle.getsockname().ok(),
+            _ => None,
+        }
+    }
+
+    /// Close the handle, if any.
+    fn close(&mut self) {
+        match self {
+            ActivatedSocket::Tcp(handle) => handle.close(()),
+            ActivatedSocket::Udp(handle) => handle.close(()),
+            ActivatedSocket::Pipe(handle) => handle.close(()),
+            ActivatedSocket::Other(_) => {}
+        }
+    }
+}
+
+/// Set FD_CLOEXEC on a file descriptor.
+fn set_cloexec(fd: crate::File) -> std::io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl crate::Loop {
+    /// Open the file descriptors passed to this process by systemd socket activation, like
+    /// sd_listen_fds_with_names(3).
+    ///
+    /// The descriptors are described by the LISTEN_FDS, LISTEN_PID and LISTEN_FDNAMES environment
+    /// variables. If they are not set, or LISTEN_PID is not the pid of this process, an empty Vec
+    /// is returned. FD_CLOEXEC is set on the descriptors, and each one is opened as a TcpHandle,
+    /// UdpHandle or PipeHandle depending on the kind of socket it is. If unset_env is true, the
+    /// environment variables are removed so that child processes do not inherit them.
+    pub fn listen_fds(&self, unset_env: bool) -> Result<Vec<ListenFd>, Box<dyn std::error::Error>> {
+        let count = std::env::var("LISTEN_FDS").ok();
+        let pid = std::env::var("LISTEN_PID").ok();
+        let names = std::env::var("LISTEN_FDNAMES").ok();
+        if unset_env {
+            std::env::remove_var("LISTEN_FDS");
+            std::env::remove_var("LISTEN_PID");
+            std::env::remove_var("LISTEN_FDNAMES");
+        }
+
+        let count = match count {
+            Some(count) => count.parse::<crate::File>()?,
+            None => return Ok(Vec::new()),
+        };
+        match pid {
+            Some(pid) if pid.parse::<u32>()? == std::process::id() => {}
+            _ => return Ok(Vec::new()),
+        }
+        if count < 0 {
+            return Err(Box::new(crate::Error::EINVAL));
+        }
+
+        // like sd_listen_fds(), keep the descriptors from leaking into child processes
+        for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+            set_cloexec(fd)?;
+        }
+
+        let mut names = names.as_deref().unwrap_or("").split(':');
+        let mut fds: Vec<ListenFd> = Vec::with_capacity(count as _);
+        for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+            let socket = match ActivatedSocket::open(self, fd) {
+                Ok(socket) => socket,
+                Err(e) => {
+                    for mut listen_fd in fds {
+                        listen_fd.socket.close();
+                    }
+                    return Err(Box::new(e));
+                }
+            };
+            let name = match names.next() {
+                Some(name) if !name.is_empty() => name.to_owned(),
+                _ => "unknown".to_owned(),
+            };
+            fds.push(ListenFd {
+                fd,
+                name,
+                local_addr: socket.local_addr(),
+                socket,
+            });
+        }
+        Ok(fds)
+    }
+}
@@ -0,0 +1,519 @@
+use crate::{HandleTrait, StreamTrait, ToStream};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+callbacks! {
+    pub TcpServerStopCB(forced: usize);
+}
+
+/// Handles the events of a single connection accepted by a TcpServer. A new handler is created
+/// for every connection by the factory passed to TcpServer::new().
+pub trait ConnectionHandler {
+    /// Called when data has been read from the connection.
+    fn on_data(&mut self, conn: &Connection, data: &[u8]);
+
+    /// Called when the peer has finished sending data. By default, the connection is closed.
+    fn on_eof(&mut self, conn: &Connection) {
+        conn.close();
+    }
+
+    /// Called when TcpServer::stop() is called. By default, nothing happens: the connection stays
+    /// open until it is closed or the stop deadline passes.
+    fn on_stop(&mut self, _conn: &Connection) {}
+
+    /// Called after the connection has been closed. error is the read error that caused the
+    /// connection to close, ETIMEDOUT if the connection was idle for too long, ECANCELED if it was
+    /// closed at the stop deadline, or None if it was closed with Connection::close().
+    fn on_close(&mut self, _conn: &Connection, _error: Option<crate::Error>) {}
+}
+
+type Factory = Box<dyn FnMut(&Connection) -> Box<dyn ConnectionHandler>>;
+
+struct ConnectionEntry {
+    tcp: crate::TcpHandle,
+    peer: Option<SocketAddr>,
+    timer: Option<crate::TimerHandle>,
+    handler: Option<Box<dyn ConnectionHandler>>,
+    error: Option<crate::Error>,
+    closing: bool,
+}
+
+struct ServerState {
+    r#loop: crate::Loop,
+    listener: crate::TcpHandle,
+    max_connections: usize,
+    idle_timeout: u64,
+    connections: HashMap<u64, ConnectionEntry>,
+    next_id: u64,
+    accept_pending: bool,
+    stopping: bool,
+    stop_timer: Option<crate::TimerHandle>,
+    stop_cb: TcpServerStopCB<'static>,
+    forced: usize,
+}
+
+struct Server {
+    state: RefCell<ServerState>,
+    factory: RefCell<Factory>,
+}
+
+/// A connection accepted by a TcpServer.
+#[derive(Clone)]
+pub struct Connection {
+    id: u64,
+    tcp: crate::TcpHandle,
+    peer: Option<SocketAddr>,
+    server: Rc<Server>,
+}
+
+impl Connection {
+    /// An id that is unique among the connections of the server.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The address of the peer, as returned by getpeername() when the connection was accepted.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// The underlying TcpHandle. The TcpServer owns the handle: do not close it or start/stop
+    /// reading directly.
+    pub fn handle(&self) -> crate::TcpHandle {
+        self.tcp
+    }
+
+    /// Write data to the connection. The data is copied, so it can be dropped as soon as write()
+    /// returns. Writing resets the idle timeout.
+    pub fn write(&self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        crate::write_copy(&mut self.tcp.to_stream(), data, |_| {})?;
+
+        self.server.reset_idle_timer(self.id);
+        Ok(())
+    }
+
+    /// Close the connection once pending writes have been flushed.
+    pub fn close(&self) {
+        self.server.close(self.id, None, false);
+    }
+}
+
+impl Server {
+    /// Run f with the handler for the given connection. The handler is taken out of the state
+    /// while it runs, so that it can call back into the server.
+    fn with_handler<F: FnOnce(&mut dyn ConnectionHandler, &Connection)>(
+        self: &Rc<Self>,
+        id: u64,
+        f: F,
+    ) {
+        let (mut handler, conn) = {
+            let mut state = self.state.borrow_mut();
+            let entry = match state.connections.get_mut(&id) {
+                Some(entry) => entry,
+                None => return,
+            };
+            let handler = match entry.handler.take() {
+                Some(handler) => handler,
+                None => return,
+            };
+            let conn = Connection {
+                id,
+                tcp: entry.tcp,
+                peer: entry.peer,
+                server: self.clone(),
+            };
+            (handler, conn)
+        };
+
+        f(handler.as_mut(), &conn);
+
+        if let Some(entry) = self.state.borrow_mut().connections.get_mut(&id) {
+            entry.handler = Some(handler);
+        }
+    }
+
+    /// Called when the listener has a new connection.
+    fn on_connection(self: &Rc<Self>) {
+        let mut state = self.state.borrow_mut();
+        if state.stopping {
+            return;
+        }
+        if state.connections.len() >= state.max_connections {
+            // Leaving the connection unaccepted makes libuv stop accepting new connections until
+            // accept() is called again, once a connection has closed.
+            state.accept_pending = true;
+            return;
+        }
+        drop(state);
+        self.accept();
+    }
+
+    /// Accept a connection, create its handler, and start reading.
+    fn accept(self: &Rc<Self>) {
+        let (r#loop, mut listener, idle_timeout) = {
+            let state = self.state.borrow();
+            (state.r#loop.clone(), state.listener, state.idle_timeout)
+        };
+
+        let mut tcp = match r#loop.tcp() {
+            Ok(tcp) => tcp,
+            Err(_) => return,
+        };
+        if listener.accept(&mut tcp.to_stream()).is_err() {
+            tcp.close(());
+            return;
+        }
+
+        let id = {
+            let mut state = self.state.borrow_mut();
+            let id = state.next_id;
+            state.next_id += 1;
+            let timer = if idle_timeout > 0 {
+                r#loop.timer().ok()
+            } else {
+                None
+            };
+            state.connections.insert(
+                id,
+                ConnectionEntry {
+                    tcp,
+                    peer: tcp.getpeername().ok(),
+                    timer,
+                    handler: None,
+                    error: None,
+                    closing: false,
+                },
+            );
+            id
+        };
+
+        let conn = Connection {
+            id,
+            tcp,
+            peer: self.state.borrow().connections[&id].peer,
+            server: self.clone(),
+        };
+        let handler = (self.factory.borrow_mut())(&conn);
+        if let Some(entry) = self.state.borrow_mut().connections.get_mut(&id) {
+            entry.handler = Some(handler);
+        }
+        self.reset_idle_timer(id);
+
+        let server = self.clone();
+        let result = tcp.read_start(
+            |_, size| crate::Buf::with_capacity(size).ok(),
+            move |_, nread: crate::Result<usize>, mut buf: crate::ReadonlyBuf| {
+                server.on_read(id, nread, &buf);
+                buf.dealloc();
+            },
+        );
+        if let Err(e) = result {
+            self.close(id, Some(e), true);
+        }
+    }
+
+    /// Called when data has been read from a connection.
+    fn on_read(self: &Rc<Self>, id: u64, nread: crate::Result<usize>, buf: &crate::ReadonlyBuf) {
+        match nread {
+            Ok(0) => {}
+            Ok(len) => {
+                self.reset_idle_timer(id);
+                self.with_handler(id, |handler, conn| handler.on_data(conn, &buf[..len]));
+            }
+            Err(crate::Error::EOF) => {
+                self.with_handler(id, |handler, conn| handler.on_eof(conn));
+            }
+            Err(e) => self.close(id, Some(e), true),
+        }
+    }
+
+    /// Restart the idle timer of a connection.
+    fn reset_idle_timer(self: &Rc<Self>, id: u64) {
+        let (mut timer, idle_timeout) = {
+            let state = self.state.borrow();
+            match state.connections.get(&id).and_then(|entry| entry.timer) {
+                Some(timer) => (timer, state.idle_timeout),
+                None => return,
+            }
+        };
+
+        let server = self.clone();
+        let _ = timer.start(idle_timeout, 0, move |_| {
+            server.close(id, Some(crate::Error::ETIMEDOUT), true)
+        });
+    }
+
+    /// Close a connection. If force is false, pending writes are flushed first.
+    fn close(self: &Rc<Self>, id: u64, error: Option<crate::Error>, force: bool) {
+        let mut tcp = {
+            let mut state = self.state.borrow_mut();
+            let entry = match state.connections.get_mut(&id) {
+                Some(entry) => entry,
+                None => return,
+            };
+            if entry.closing && !force {
+                return;
+            }
+            entry.closing = true;
+            if entry.error.is_none() {
+                entry.error = error;
+            }
+            if force {
+                if let Some(mut timer) = entry.timer.take() {
+                    timer.close(());
+                }
+            }
+            entry.tcp
+        };
+
+        if tcp.is_closing() {
+            return;
+        }
+
+        let server = self.clone();
+        if !force && tcp.is_writable() {
+            let result = tcp.shutdown(move |req: crate::ShutdownReq, _| {
+                let mut stream = req.handle();
+                if !stream.is_closing() {
+                    let server = server.clone();
+                    stream.close(move |_| server.on_close(id));
+                }
+            });
+            if result.is_ok() {
+                return;
+            }
+        }
+
+        let server = self.clone();
+        tcp.close(move |_| server.on_close(id));
+    }
+
+    /// Called once a connection's handle has been closed.
+    fn on_close(self: &Rc<Self>, id: u64) {
+        let mut entry = match self.state.borrow_mut().connections.remove(&id) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some(mut timer) = entry.timer.take() {
+            timer.close(());
+        }
+        if let Some(mut handler) = entry.handler.take() {
+            let conn = Connection {
+                id,
+                tcp: entry.tcp,
+                peer: entry.peer,
+                server: self.clone(),
+            };
+            handler.on_close(&conn, entry.error);
+        }
+
+        let mut state = self.state.borrow_mut();
+        if state.stopping {
+            if state.connections.is_empty() {
+                drop(state);
+                self.finish_stop();
+            }
+        } else if state.accept_pending {
+            state.accept_pending = false;
+            drop(state);
+            self.accept();
+        }
+    }
+
+    /// Called once all connections have closed after stop().
+    fn finish_stop(self: &Rc<Self>) {
+        let (mut cb, forced) = {
+            let mut state = self.state.borrow_mut();
+            if let Some(mut timer) = state.stop_timer.take() {
+                timer.close(());
+            }
+            (std::mem::take(&mut state.stop_cb), state.forced)
+        };
+        cb.call(forced);
+    }
+}
+
+/// A TCP server that accepts connections and hands each one to a ConnectionHandler.
+///
+/// The server can limit the number of concurrent connections: once the limit is reached, it stops
+/// accepting new connections until one of the existing ones closes. Connections that are idle for
+/// longer than the idle timeout are closed.
+///
+/// ```no_run
+/// # use libuv::{Connection, ConnectionHandler, Loop, TcpServer};
+/// struct Echo;
+///
+/// impl ConnectionHandler for Echo {
+///     fn on_data(&mut self, conn: &Connection, data: &[u8]) {
+///         let _ = conn.write(data);
+///     }
+/// }
+///
+/// let r#loop = Loop::default().unwrap();
+/// let server = TcpServer::new(&r#loop, |_: &Connection| Echo)
+///     .unwrap()
+///     .max_connections(100)
+///     .idle_timeout(30_000);
+/// server.listen(&"0.0.0.0:7000".parse().unwrap(), 128).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct TcpServer {
+    server: Rc<Server>,
+}
+
+impl TcpServer {
+    /// Create a new TcpServer. factory is called for every accepted connection to create the
+    /// ConnectionHandler for it. By default, there is no connection limit and no idle timeout.
+    pub fn new<F, H>(r#loop: &crate::Loop, mut factory: F) -> crate::Result<TcpServer>
+    where
+        F: FnMut(&Connection) -> H + 'static,
+        H: ConnectionHandler + 'static,
+    {
+        let listener = r#loop.tcp()?;
+        let factory: Factory = Box::new(move |conn| Box::new(factory(conn)));
+        Ok(TcpServer {
+            server: Rc::new(Server {
+                state: RefCell::new(ServerState {
+                    r#loop: r#loop.clone(),
+                    listener,
+                    max_connections: usize::MAX,
+                    idle_timeout: 0,
+                    connections: HashMap::new(),
+                    next_id: 0,
+                    accept_pending: false,
+                    stopping: false,
+                    stop_timer: None,
+                    stop_cb: ().into(),
+                    forced: 0,
+                }),
+                factory: RefCell::new(factory),
+            }),
+        })
+    }
+
+    /// Set the maximum number of concurrent connections.
+    pub fn max_connections(self, max_connections: usize) -> TcpServer {
+        self.server.state.borrow_mut().max_connections = max_connections.max(1);
+        self
+    }
+
+    /// Close connections that have not read or written any data for this many milliseconds. An
+    /// idle_timeout of 0 disables the timeout. Only affects connections accepted afterwards.
+    pub fn idle_timeout(self, idle_timeout: u64) -> TcpServer {
+        self.server.state.borrow_mut().idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Bind to the given address and start accepting connections.
+    pub fn listen(
+        &self,
+        addr: &SocketAddr,
+        backlog: i32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut listener = self.server.state.borrow().listener;
+        listener.bind(addr, crate::TcpBindFlags::empty())?;
+
+        let server = self.server.clone();
+        listener.listen(backlog, move |_, status: crate::Result<u32>| {
+            if status.is_ok() {
+                server.on_connection();
+            }
+        })?;
+        Ok(())
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr, Box<dyn std::error::Error>> {
+        self.server.state.borrow().listener.getsockname()
+    }
+
+    /// The number of open connections.
+    pub fn connection_count(&self) -> usize {
+        self.server.state.borrow().connections.len()
+    }
+
+    /// The open connections.
+    pub fn connections(&self) -> Vec<Connection> {
+        self.server
+            .state
+            .borrow()
+            .connections
+            .iter()
+            .map(|(id, entry)| Connection {
+                id: *id,
+                tcp: entry.tcp,
+                peer: entry.peer,
+                server: self.server.clone(),
+            })
+            .collect()
+    }
+
+    /// Gracefully stop the server: stop accepting connections, let the open connections finish,
+    /// and, after deadline milliseconds, close any connections that are still open.
+    ///
+    /// The ConnectionHandler of every open connection is notified with on_stop(). Once all
+    /// connections have closed, the callback is called with the number of connections that had to
+    /// be closed at the deadline.
+    pub fn stop<CB: Into<TcpServerStopCB<'static>>>(&self, deadline: u64, cb: CB) {
+        let (mut listener, r#loop, ids) = {
+            let mut state = self.server.state.borrow_mut();
+            if state.stopping {
+                return;
+            }
+            state.stopping = true;
+            state.stop_cb = cb.into();
+            let ids: Vec<u64> = state.connections.keys().copied().collect();
+            (state.listener, state.r#loop.clone(), ids)
+        };
+        if !listener.is_closing() {
+            listener.close(());
+        }
+
+        if ids.is_empty() {
+            self.server.finish_stop();
+            return;
+        }
+
+        for id in ids {
+            self.server
+                .with_handler(id, |handler, conn| handler.on_stop(conn));
+        }
+
+        if let Ok(mut timer) = r#loop.timer() {
+            let server = self.server.clone();
+            let result = timer.start(deadline, 0, move |mut timer: crate::TimerHandle| {
+                timer.close(());
+                let ids: Vec<u64> = {
+                    let mut state = server.state.borrow_mut();
+                    state.stop_timer = None;
+                    state.connections.keys().copied().collect()
+                };
+                server.state.borrow_mut().forced += ids.len();
+                for id in ids {
+                    server.close(id, Some(crate::Error::ECANCELED), true);
+                }
+            });
+            match result {
+                Ok(_) => self.server.state.borrow_mut().stop_timer = Some(timer),
+                Err(_) => timer.close(()),
+            }
+        }
+    }
+}
+
+impl crate::Loop {
+    /// Create a new TcpServer on this loop. See TcpServer::new().
+    pub fn tcp_server<F, H>(&self, factory: F) -> crate::Result<TcpServer>
+    where
+        F: FnMut(&Connection) -> H + 'static,
+        H: ConnectionHandler + 'static,
+    {
+        TcpServer::new(self, factory)
+    }
+}
@@ -0,0 +1,28 @@
+pub(crate) enum AddlReqData<'a> {
+    ConnectData(crate::ConnectDataFields<'a>),
+    FsData(crate::FsDataFields<'a>),
+    GetAddrInfoData(crate::GetAddrInfoDataFields<'a>),
+    GetNameInfoData(crate::GetNameInfoDataFields<'a>),
+    RandomData(crate::RandomDataFields<'a>),
+    ShutdownData(crate::ShutdownDataFields<'a>),
+    UdpSendData(crate::UdpSendDataFields<'a>),
+    WorkData(crate::WorkDataFields<'a>),
+    WriteData(crate::WriteDataFields<'a>),
+}
+
+impl AddlReqData<'_> {
+    /// The type of request that this data is attached to.
+    pub(crate) fn req_type(&self) -> crate::ReqType {
+        match self {
+            AddlReqData::ConnectData(_) => crate::ReqType::CONNECT,
+            AddlReqData::FsData(_) => crate::ReqType::FS,
+            AddlReqData::GetAddrInfoData(_) => crate::ReqType::GETADDRINFO,
+            AddlReqData::GetNameInfoData(_) => crate::ReqType::GETNAMEINFO,
+            AddlReqData::RandomData(_) => crate::ReqType::RANDOM,
+            AddlReqData::ShutdownData(_) => crate::ReqType::SHUTDOWN,
+            AddlReqData::UdpSendData(_) => crate::ReqType::UDP_SEND,
+            AddlReqData::WorkData(_) => crate::ReqType::WORK,
+            AddlReqData::WriteData(_) => crate::ReqType::WRITE,
+        }
+    }
+}
@@ -15,7 +15,7 @@ pub(crate) extern "C" fn uv_connect_cb(req: *mut uv_connect_t, status: std::os::
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::ConnectData(d) = &mut *dataptr {
+            if let super::ConnectData(d) = &mut (*dataptr).addl {
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
                 } else {
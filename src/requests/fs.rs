@@ -12,14 +12,23 @@ callbacks! {
 /// Additional data stored on the request
 pub(crate) struct FsDataFields<'a> {
     fs_cb: FsCB<'a>,
+
+    /// Milliseconds to delay the callback, set by an injected FsFault::Delay.
+    #[cfg(feature = "testing")]
+    fault_delay: u64,
 }
 
 /// Callback for various fs functions
 pub(crate) extern "C" fn uv_fs_cb(req: *mut uv_fs_t) {
+    #[cfg(feature = "testing")]
+    if crate::testing::fs_faults::on_fs_complete(req) {
+        return;
+    }
+
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::FsData(d) = &mut *dataptr {
+            if let super::FsData(d) = &mut (*dataptr).addl {
                 d.fs_cb.call(req.into_inner());
             }
         }
@@ -46,7 +55,14 @@ impl FsReq {
         }
 
         let fs_cb = cb.into();
-        crate::Req::initialize_data(uv_handle!(req), super::FsData(FsDataFields { fs_cb }));
+        crate::Req::initialize_data(
+            uv_handle!(req),
+            super::FsData(FsDataFields {
+                fs_cb,
+                #[cfg(feature = "testing")]
+                fault_delay: 0,
+            }),
+        );
 
         Ok(FsReq { req })
     }
@@ -123,6 +139,25 @@ impl FsReq {
         }
     }
 
+    /// Set the delay from an injected FsFault::Delay.
+    #[cfg(feature = "testing")]
+    pub(crate) fn set_fault_delay(&mut self, delay: u64) {
+        let dataptr = crate::Req::get_data(uv_handle!(self.req));
+        if let Some(super::FsData(d)) = unsafe { dataptr.as_mut() }.map(|data| &mut data.addl) {
+            d.fault_delay = delay;
+        }
+    }
+
+    /// Returns the delay from an injected FsFault::Delay, and resets it to zero.
+    #[cfg(feature = "testing")]
+    pub(crate) fn take_fault_delay(&mut self) -> u64 {
+        let dataptr = crate::Req::get_data(uv_handle!(self.req));
+        match unsafe { dataptr.as_mut() }.map(|data| &mut data.addl) {
+            Some(super::FsData(d)) => std::mem::replace(&mut d.fault_delay, 0),
+            _ => 0,
+        }
+    }
+
     /// Free up memory associated with this request. If you are using one of the async fs_*
     /// functions, this will be called automatically after the callback runs.
     pub fn destroy(&mut self) {
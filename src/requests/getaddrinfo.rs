@@ -1,4 +1,4 @@
-use crate::{FromInner, Inner, IntoInner};
+use crate::{FromInner, Inner, IntoInner, ToReq};
 use std::ffi::CString;
 use uv::{addrinfo, uv_freeaddrinfo, uv_getaddrinfo, uv_getaddrinfo_t};
 
@@ -20,7 +20,7 @@ extern "C" fn uv_getaddrinfo_cb(req: *mut uv_getaddrinfo_t, status: i32, res: *m
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::GetAddrInfoData(d) = &mut *dataptr {
+            if let super::GetAddrInfoData(d) = &mut (*dataptr).addl {
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
                 } else {
@@ -126,7 +126,7 @@ impl crate::Loop {
         &self,
         node: Option<&str>,
         service: Option<&str>,
-        hints: Option<crate::AddrInfo>,
+        hints: Option<crate::Hints>,
         cb: CB,
     ) -> Result<GetAddrInfoReq, Box<dyn std::error::Error>> {
         let cb = cb.into();
@@ -134,6 +134,7 @@ impl crate::Loop {
         let node = node.map(CString::new).transpose()?;
         let service = service.map(CString::new).transpose()?;
         let mut req = GetAddrInfoReq::new(cb)?;
+        req.to_req().set_loop(self);
         let hints = hints.map(|h| h.into_inner());
         let result = crate::uvret(unsafe {
             uv_getaddrinfo(
@@ -168,13 +169,13 @@ impl crate::Loop {
     ///
     /// Either node or service may be None but not both.
     ///
-    /// hints is a AddrInfo with additional address type constraints, or None. Consult man -s 3
-    /// getaddrinfo for more details.
+    /// hints constrains the address types that are returned, or None. Consult man -s 3 getaddrinfo
+    /// for more details.
     pub fn getaddrinfo<CB: Into<GetAddrInfoCB<'static>>>(
         &self,
         node: Option<&str>,
         service: Option<&str>,
-        hints: Option<crate::AddrInfo>,
+        hints: Option<crate::Hints>,
         cb: CB,
     ) -> Result<GetAddrInfoReq, Box<dyn std::error::Error>> {
         self._getaddrinfo(node, service, hints, cb)
@@ -184,15 +185,15 @@ impl crate::Loop {
     ///
     /// Either node or service may be None but not both.
     ///
-    /// hints is a AddrInfo with additional address type constraints, or None. Consult man -s 3
-    /// getaddrinfo for more details.
+    /// hints constrains the address types that are returned, or None. Consult man -s 3 getaddrinfo
+    /// for more details.
     ///
     /// Returns an iterator over resulting AddrInfo structs.
     pub fn getaddrinfo_sync(
         &self,
         node: Option<&str>,
         service: Option<&str>,
-        hints: Option<crate::AddrInfo>,
+        hints: Option<crate::Hints>,
     ) -> Result<Vec<crate::AddrInfo>, Box<dyn std::error::Error>> {
         self._getaddrinfo(node, service, hints, ())
             .map(|req| req.addrinfos())
@@ -1,7 +1,30 @@
-use crate::{FromInner, Inner, IntoInner};
+use crate::{FromInner, Inner, IntoInner, ToReq};
 use std::ffi::CStr;
 use std::net::SocketAddr;
-use uv::{uv_getnameinfo, uv_getnameinfo_t};
+use uv::{
+    uv_getnameinfo, uv_getnameinfo_t, NI_DGRAM, NI_NAMEREQD, NI_NOFQDN, NI_NUMERICHOST,
+    NI_NUMERICSERV,
+};
+
+bitflags! {
+    /// Flags to getnameinfo(). Consult man -s 3 getnameinfo for more details.
+    pub struct NameInfoFlags: u32 {
+        /// Return only the hostname part of the fully qualified domain name for local hosts.
+        const NOFQDN = NI_NOFQDN as _;
+
+        /// Return the numeric form of the hostname.
+        const NUMERICHOST = NI_NUMERICHOST as _;
+
+        /// Return an error if the hostname cannot be determined.
+        const NAMEREQD = NI_NAMEREQD as _;
+
+        /// Return the numeric form of the service (port number).
+        const NUMERICSERV = NI_NUMERICSERV as _;
+
+        /// The service is datagram (UDP) based rather than stream (TCP) based.
+        const DGRAM = NI_DGRAM as _;
+    }
+}
 
 callbacks! {
     pub GetNameInfoCB(
@@ -27,9 +50,18 @@ extern "C" fn uv_getnameinfo_cb(
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::GetNameInfoData(d) = &mut *dataptr {
-                let hostname = CStr::from_ptr(hostname).to_string_lossy().into_owned();
-                let service = CStr::from_ptr(service).to_string_lossy().into_owned();
+            if let super::GetNameInfoData(d) = &mut (*dataptr).addl {
+                // hostname and service are null if the lookup failed
+                let hostname = if hostname.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(hostname).to_string_lossy().into_owned()
+                };
+                let service = if service.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(service).to_string_lossy().into_owned()
+                };
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
                 } else {
@@ -144,7 +176,7 @@ impl crate::Loop {
     fn _getnameinfo<CB: Into<GetNameInfoCB<'static>>>(
         &self,
         addr: &SocketAddr,
-        flags: u32,
+        flags: NameInfoFlags,
         cb: CB,
     ) -> Result<GetNameInfoReq, Box<dyn std::error::Error>> {
         let mut sockaddr: uv::sockaddr_storage = unsafe { std::mem::zeroed() };
@@ -153,13 +185,14 @@ impl crate::Loop {
         let cb = cb.into();
         let uv_cb = use_c_callback!(uv_getnameinfo_cb, cb);
         let mut req = GetNameInfoReq::new(cb)?;
+        req.to_req().set_loop(self);
         let result = crate::uvret(unsafe {
             uv_getnameinfo(
                 self.into_inner(),
                 req.inner(),
                 uv_cb,
                 uv_handle!(&sockaddr),
-                flags as _,
+                flags.bits() as _,
             )
         });
         if result.is_err() {
@@ -172,12 +205,10 @@ impl crate::Loop {
     ///
     /// If successful, the callback will get called sometime in the future with the lookup result.
     /// Consult man -s 3 getnameinfo for more details.
-    ///
-    /// flags is the bitwise OR of NI_* constants
     pub fn getnameinfo<CB: Into<GetNameInfoCB<'static>>>(
         &self,
         addr: &SocketAddr,
-        flags: u32,
+        flags: NameInfoFlags,
         cb: CB,
     ) -> Result<GetNameInfoReq, Box<dyn std::error::Error>> {
         self._getnameinfo(addr, flags, cb)
@@ -186,12 +217,10 @@ impl crate::Loop {
     /// Synchronous getnameinfo(3).
     ///
     /// If successful, will return a tuple of (host, service) Strings.
-    ///
-    /// flags is the bitwise OR of NI_* constants
     pub fn getnameinfo_sync(
         &self,
         addr: &SocketAddr,
-        flags: u32,
+        flags: NameInfoFlags,
     ) -> Result<(String, String), Box<dyn std::error::Error>> {
         self._getnameinfo(addr, flags, ()).map(|mut req| {
             let res = (req.host(), req.service());
@@ -1,4 +1,4 @@
-use crate::{FromInner, Inner, IntoInner};
+use crate::{FromInner, Inner, IntoInner, ToReq};
 use uv::{uv_random, uv_random_t};
 
 callbacks! {
@@ -24,7 +24,7 @@ extern "C" fn uv_random_cb(
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::RandomData(d) = &mut *dataptr {
+            if let super::RandomData(d) = &mut (*dataptr).addl {
                 let buf = Vec::from_raw_parts(buf as _, buflen as _, buflen as _);
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
@@ -137,6 +137,7 @@ impl crate::Loop {
         cb: CB,
     ) -> crate::Result<RandomReq> {
         let mut req = RandomReq::new(cb)?;
+        req.to_req().set_loop(self);
         let mut buf = std::mem::ManuallyDrop::new(Vec::<u8>::with_capacity(buflen));
         let result = crate::uvret(unsafe {
             uv_random(
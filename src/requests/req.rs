@@ -1,7 +1,10 @@
 include!("./req_types.inc.rs");
 
 use crate::{FromInner, Inner, IntoInner};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CStr;
+use std::rc::Rc;
 use uv::{
     uv_cancel, uv_req_get_data, uv_req_get_type, uv_req_set_data, uv_req_t, uv_req_type_name,
 };
@@ -23,6 +26,17 @@ impl std::fmt::Display for ReqType {
     }
 }
 
+/// The number of pending requests of each type on a loop.
+pub(crate) type PendingReqs = Rc<RefCell<HashMap<ReqType, usize>>>;
+
+/// Data that we need to track with the request.
+pub(crate) struct ReqData<'a> {
+    /// The pending request counts of the loop that the request was started on, once it has been
+    /// started.
+    pending: Option<PendingReqs>,
+    pub(crate) addl: super::AddlReqData<'a>,
+}
+
 /// Req is the base type for all libuv requests
 #[derive(Clone, Copy)]
 pub struct Req {
@@ -31,20 +45,50 @@ pub struct Req {
 
 impl Req {
     /// Initialize the request's data.
-    pub(crate) fn initialize_data(req: *mut uv_req_t, data: super::ReqData) {
+    pub(crate) fn initialize_data(req: *mut uv_req_t, addl: super::AddlReqData) {
+        let data = ReqData {
+            pending: None,
+            addl,
+        };
         let ptr = Box::into_raw(Box::new(data));
         unsafe { uv_req_set_data(req, ptr as _) }
     }
 
     /// Retrieve the request's data.
-    pub(crate) fn get_data<'a>(req: *mut uv_req_t) -> *mut super::ReqData<'a> {
+    pub(crate) fn get_data<'a>(req: *mut uv_req_t) -> *mut ReqData<'a> {
         unsafe { uv_req_get_data(req) as _ }
     }
 
+    /// Record that the request is being started on a loop. Until it is destroyed, the request is
+    /// counted in the loop's pending requests.
+    pub(crate) fn set_loop(&self, r#loop: &crate::Loop) {
+        let dataptr = Req::get_data(self.req);
+        if let Some(data) = unsafe { dataptr.as_mut() } {
+            if data.pending.is_none() {
+                if let Some(pending) = r#loop.pending_reqs_counter() {
+                    *pending
+                        .borrow_mut()
+                        .entry(data.addl.req_type())
+                        .or_insert(0) += 1;
+                    data.pending = Some(pending);
+                }
+            }
+        }
+    }
+
     /// Free the request's data.
     pub(crate) fn free_data(req: *mut uv_req_t) {
         let ptr = Req::get_data(req);
-        std::mem::drop(unsafe { Box::from_raw(ptr) });
+        let data = unsafe { Box::from_raw(ptr) };
+
+        // the counts are shared with the loop rather than looked up through it, because the loop
+        // may already be gone
+        if let Some(pending) = &data.pending {
+            if let Some(count) = pending.borrow_mut().get_mut(&data.addl.req_type()) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        std::mem::drop(data);
         unsafe { uv_req_set_data(req, std::ptr::null_mut()) };
     }
 }
@@ -18,7 +18,7 @@ pub(crate) extern "C" fn uv_udp_send_cb(req: *mut uv_udp_send_t, status: std::os
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::UdpSendData(d) = &mut *dataptr {
+            if let super::UdpSendData(d) = &mut (*dataptr).addl {
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
                 } else {
@@ -79,7 +79,7 @@ impl UdpSendReq {
     pub fn destroy(&mut self) {
         let dataptr = crate::Req::get_data(uv_handle!(self.req));
         if !dataptr.is_null() {
-            if let super::UdpSendData(d) = unsafe { &mut *dataptr } {
+            if let super::UdpSendData(d) = unsafe { &mut (*dataptr).addl } {
                 if !d.bufs_ptr.is_null() {
                     // This will destroy the Vec<uv_buf_t>, but will not actually deallocate the
                     // uv_buf_t's themselves. That's up to the user to do.
@@ -1,4 +1,4 @@
-use crate::{FromInner, Inner, IntoInner};
+use crate::{FromInner, Inner, IntoInner, ToReq};
 use uv::{uv_queue_work, uv_work_t};
 
 callbacks! {
@@ -17,7 +17,7 @@ extern "C" fn uv_work_cb(req: *mut uv_work_t) {
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::WorkData(d) = &mut *dataptr {
+            if let super::WorkData(d) = &mut (*dataptr).addl {
                 d.work_cb.call(req.into_inner());
             }
         }
@@ -28,7 +28,7 @@ extern "C" fn uv_after_work_cb(req: *mut uv_work_t, status: i32) {
     let dataptr = crate::Req::get_data(uv_handle!(req));
     if !dataptr.is_null() {
         unsafe {
-            if let super::WorkData(d) = &mut *dataptr {
+            if let super::WorkData(d) = &mut (*dataptr).addl {
                 let status = if status < 0 {
                     Err(crate::Error::from_inner(status as uv::uv_errno_t))
                 } else {
@@ -133,6 +133,7 @@ impl crate::Loop {
         let work_cb = work_cb.into();
         let uv_work_cb = use_c_callback!(uv_work_cb, work_cb);
         let mut req = WorkReq::new(work_cb, after_work_cb)?;
+        req.to_req().set_loop(self);
         let uv_after_work_cb = Some(uv_after_work_cb as _);
         let result = crate::uvret(unsafe {
             uv_queue_work(self.into_inner(), req.inner(), uv_work_cb, uv_after_work_cb)
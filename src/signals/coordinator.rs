@@ -0,0 +1,287 @@
+use crate::{HandleTrait, Signal, SignalSet, TimerHandle};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// How a shutdown finished.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ShutdownOutcome {
+    /// Every cleanup hook finished before the deadline.
+    Completed,
+
+    /// The deadline expired. The names of the hooks that had not finished are included. The loop
+    /// has been stopped.
+    TimedOut(Vec<String>),
+
+    /// A second termination signal arrived before the cleanup hooks finished. The names of the
+    /// hooks that had not finished are included. The loop has been stopped.
+    Forced(Vec<String>),
+}
+
+callbacks! {
+    pub ShutdownHookCB(done: ShutdownDone);
+    pub ShutdownCompleteCB(outcome: ShutdownOutcome);
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Phase {
+    Running,
+    ShuttingDown,
+    Done,
+}
+
+struct ShutdownState {
+    signals: SignalSet,
+    timer: TimerHandle,
+    deadline: u64,
+    phase: Phase,
+    hooks: Vec<(String, ShutdownHookCB<'static>)>,
+    pending: Vec<(usize, String)>,
+    complete_cb: ShutdownCompleteCB<'static>,
+}
+
+type State = Rc<RefCell<ShutdownState>>;
+
+/// Passed to a cleanup hook. Call finish() once the hook's cleanup is complete. Dropping it also
+/// finishes the hook.
+pub struct ShutdownDone {
+    state: State,
+    id: usize,
+    finished: Cell<bool>,
+}
+
+impl ShutdownDone {
+    /// Mark the hook as finished. Only the first call has any effect.
+    pub fn finish(&self) {
+        if self.finished.replace(true) {
+            return;
+        }
+
+        let all_finished = {
+            let mut state = self.state.borrow_mut();
+            state.pending.retain(|(id, _)| *id != self.id);
+            state.phase == Phase::ShuttingDown && state.pending.is_empty()
+        };
+        if all_finished {
+            complete(&self.state, ShutdownOutcome::Completed);
+        }
+    }
+}
+
+impl Drop for ShutdownDone {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// End the shutdown: release the coordinator's handles and report the outcome. If the outcome is
+/// not Completed, every remaining handle is closed and the loop is stopped.
+fn complete(state: &State, outcome: ShutdownOutcome) {
+    let (mut timer, mut cb) = {
+        let mut s = state.borrow_mut();
+        if s.phase == Phase::Done {
+            return;
+        }
+        s.phase = Phase::Done;
+        s.hooks.clear();
+        s.signals.close();
+        (s.timer, std::mem::take(&mut s.complete_cb))
+    };
+    if !timer.is_closing() {
+        timer.close(());
+    }
+
+    let force = outcome != ShutdownOutcome::Completed;
+    cb.call(outcome);
+
+    if force {
+        let mut r#loop = timer.get_loop();
+        r#loop.close_all();
+        r#loop.stop();
+    }
+}
+
+/// The names of the hooks that have not finished.
+fn pending_names(state: &State) -> Vec<String> {
+    let state = state.borrow();
+    state.pending.iter().map(|(_, name)| name.clone()).collect()
+}
+
+/// Begin the shutdown: start the deadline and call every hook.
+fn begin(state: &State) {
+    let (hooks, mut timer, deadline) = {
+        let mut s = state.borrow_mut();
+        match s.phase {
+            Phase::Running => {}
+            Phase::ShuttingDown => {
+                drop(s);
+                let pending = pending_names(state);
+                return complete(state, ShutdownOutcome::Forced(pending));
+            }
+            Phase::Done => return,
+        }
+        s.phase = Phase::ShuttingDown;
+        let hooks = std::mem::take(&mut s.hooks);
+
+        // every hook is pending before any of them run, so that a hook that finishes right away
+        // does not end the shutdown early
+        s.pending = hooks
+            .iter()
+            .enumerate()
+            .map(|(id, (name, _))| (id, name.clone()))
+            .collect();
+        (hooks, s.timer, s.deadline)
+    };
+
+    if hooks.is_empty() {
+        return complete(state, ShutdownOutcome::Completed);
+    }
+
+    let timer_state = state.clone();
+    let result = timer.start(deadline, 0, move |_| {
+        let pending = pending_names(&timer_state);
+        complete(&timer_state, ShutdownOutcome::TimedOut(pending));
+    });
+    if result.is_err() {
+        // without a deadline, the hooks simply run to completion
+        timer.close(());
+    }
+
+    for (id, (_, mut cb)) in hooks.into_iter().enumerate() {
+        let done = ShutdownDone {
+            state: state.clone(),
+            id,
+            finished: Cell::new(false),
+        };
+        cb.call(done);
+    }
+}
+
+/// Coordinates a graceful shutdown when the process receives SIGINT or SIGTERM.
+///
+/// Components register cleanup hooks with add_hook(). On the first termination signal (or a call
+/// to shutdown()), every hook is called with a ShutdownDone token, which the hook finishes once
+/// its cleanup is complete - usually from the callback of some asynchronous operation, such as
+/// closing a server or flushing a file. Once all of the hooks have finished, the coordinator
+/// closes its own handles and reports ShutdownOutcome::Completed, so the loop can exit normally.
+///
+/// If the hooks do not finish before the deadline, or if a second termination signal arrives in
+/// the meantime, the coordinator gives up: every handle in the loop is closed and the loop is
+/// stopped, so that run() returns. Loop::shutdown_gracefully() can be used afterwards to finish
+/// closing the handles.
+///
+/// The coordinator does not keep the loop alive by itself while it waits for a signal.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{ShutdownCoordinator, ShutdownDone, ShutdownOutcome};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let mut server = r#loop.tcp().unwrap();
+/// // ... bind and listen ...
+///
+/// let coordinator = ShutdownCoordinator::new(&r#loop)
+///     .unwrap()
+///     .deadline(5000)
+///     .on_complete(|outcome: ShutdownOutcome| println!("shutdown: {:?}", outcome));
+/// coordinator
+///     .add_hook("server", move |done: ShutdownDone| {
+///         server.close(move |_| done.finish());
+///     })
+///     .unwrap();
+///
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    state: State,
+}
+
+impl ShutdownCoordinator {
+    /// Create a coordinator that watches for SIGINT and SIGTERM, with a deadline of 10 seconds.
+    pub fn new(r#loop: &crate::Loop) -> crate::Result<ShutdownCoordinator> {
+        let timer = r#loop.timer()?;
+        let signals = match SignalSet::new(r#loop, &[Signal::SIGINT, Signal::SIGTERM]) {
+            Ok(signals) => signals,
+            Err(e) => {
+                let mut timer = timer;
+                timer.close(());
+                return Err(e);
+            }
+        };
+
+        let state = Rc::new(RefCell::new(ShutdownState {
+            signals: signals.clone(),
+            timer,
+            deadline: 10_000,
+            phase: Phase::Running,
+            hooks: Vec::new(),
+            pending: Vec::new(),
+            complete_cb: ().into(),
+        }));
+
+        let signal_state = state.clone();
+        if let Err(e) = signals.start(move |_, _| begin(&signal_state)) {
+            signals.close();
+            let mut timer = timer;
+            timer.close(());
+            return Err(e);
+        }
+        signals.unref();
+
+        Ok(ShutdownCoordinator { state })
+    }
+
+    /// Set how long the cleanup hooks have to finish, in milliseconds.
+    pub fn deadline(self, deadline: u64) -> ShutdownCoordinator {
+        self.state.borrow_mut().deadline = deadline;
+        self
+    }
+
+    /// Set the callback that reports how the shutdown finished.
+    pub fn on_complete<CB: Into<ShutdownCompleteCB<'static>>>(self, cb: CB) -> ShutdownCoordinator {
+        self.state.borrow_mut().complete_cb = cb.into();
+        self
+    }
+
+    /// Register a cleanup hook. Hooks are called in the order that they were registered, but they
+    /// run concurrently: the next hook is called as soon as the previous one returns. The name is
+    /// used to report hooks that did not finish. Returns EALREADY if the shutdown has already
+    /// begun.
+    pub fn add_hook<CB: Into<ShutdownHookCB<'static>>>(
+        &self,
+        name: &str,
+        cb: CB,
+    ) -> crate::Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.phase != Phase::Running {
+            return Err(crate::Error::EALREADY);
+        }
+        state.hooks.push((name.to_owned(), cb.into()));
+        Ok(())
+    }
+
+    /// Begin the shutdown, as if a termination signal had arrived. Calling it while the shutdown
+    /// is in progress forces it, like a second signal.
+    pub fn shutdown(&self) {
+        begin(&self.state);
+    }
+
+    /// Returns true if the shutdown has begun.
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.borrow().phase != Phase::Running
+    }
+
+    /// Stop watching for signals and release the coordinator's handles without running the hooks.
+    pub fn close(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.phase == Phase::Done {
+            return;
+        }
+        state.phase = Phase::Done;
+        state.hooks.clear();
+        state.signals.close();
+        if !state.timer.is_closing() {
+            state.timer.close(());
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod signum;
+pub use signum::*;
+
+pub mod signal_set;
+pub use signal_set::*;
+
+pub mod coordinator;
+pub use coordinator::*;
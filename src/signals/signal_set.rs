@@ -0,0 +1,133 @@
+use crate::{HandleTrait, Signal, SignalHandle};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+callbacks! {
+    pub SignalSetCB(set: SignalSet, signal: Signal);
+}
+
+struct SignalSetState {
+    handles: Vec<SignalHandle>,
+    signals: Vec<Signal>,
+    cb: SignalSetCB<'static>,
+}
+
+/// Watches several signals with a single callback, which receives the signal that arrived.
+///
+/// A SignalSet holds one SignalHandle per signal. The handles stay open until close() is called.
+#[derive(Clone)]
+pub struct SignalSet {
+    state: Rc<RefCell<SignalSetState>>,
+}
+
+impl SignalSet {
+    /// Create a set that watches the given signals, such as Signal::SIGINT and Signal::SIGTERM.
+    /// The set does not watch anything until start() is called.
+    pub fn new(r#loop: &crate::Loop, signals: &[Signal]) -> crate::Result<SignalSet> {
+        let mut handles = Vec::with_capacity(signals.len());
+        for _ in signals {
+            match r#loop.signal() {
+                Ok(handle) => handles.push(handle),
+                Err(e) => {
+                    for mut handle in handles {
+                        handle.close(());
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(SignalSet {
+            state: Rc::new(RefCell::new(SignalSetState {
+                handles,
+                signals: signals.to_vec(),
+                cb: ().into(),
+            })),
+        })
+    }
+
+    /// Start watching the signals. If start() is called again, the callback is replaced.
+    pub fn start<CB: Into<SignalSetCB<'static>>>(&self, cb: CB) -> crate::Result<()> {
+        let (handles, signals) = {
+            let mut state = self.state.borrow_mut();
+            state.cb = cb.into();
+            (state.handles.clone(), state.signals.clone())
+        };
+
+        for (mut handle, signal) in handles.into_iter().zip(signals) {
+            let set = self.clone();
+            let result = handle.start(move |_, signal| set.dispatch(signal), signal);
+            if let Err(e) = result {
+                let _ = self.stop();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching the signals. The set can be started again.
+    pub fn stop(&self) -> crate::Result<()> {
+        let handles = self.state.borrow().handles.clone();
+        let mut result = Ok(());
+        for mut handle in handles {
+            if let Err(e) = handle.stop() {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Close the signal handles. The set cannot be used afterwards.
+    pub fn close(&self) {
+        let (handles, cb) = {
+            let mut state = self.state.borrow_mut();
+            (
+                std::mem::take(&mut state.handles),
+                std::mem::take(&mut state.cb),
+            )
+        };
+        for mut handle in handles {
+            if !handle.is_closing() {
+                handle.close(());
+            }
+        }
+
+        // the callback may hold a clone of the set, which would keep the state alive; it is dropped
+        // once the state is no longer borrowed
+        drop(cb);
+    }
+
+    /// The signals that are being watched.
+    pub fn signals(&self) -> Vec<Signal> {
+        self.state.borrow().signals.clone()
+    }
+
+    /// Reference the signal handles, so that they keep the loop alive. This is the default.
+    pub fn r#ref(&self) {
+        for mut handle in self.state.borrow().handles.iter().copied() {
+            handle.r#ref();
+        }
+    }
+
+    /// Unreference the signal handles, so that the set does not keep the loop alive by itself.
+    pub fn unref(&self) {
+        for mut handle in self.state.borrow().handles.iter().copied() {
+            handle.unref();
+        }
+    }
+
+    /// Call the callback. It is taken out of the state while it runs, so that it can use the set.
+    fn dispatch(&self, signal: Signal) {
+        let mut cb = std::mem::take(&mut self.state.borrow_mut().cb);
+        cb.call(self.clone(), signal);
+        let mut state = self.state.borrow_mut();
+        state.cb.restore(cb);
+    }
+}
+
+impl crate::Loop {
+    /// Create a SignalSet for the given signals.
+    pub fn signal_set(&self, signals: &[Signal]) -> crate::Result<SignalSet> {
+        SignalSet::new(self, signals)
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The number of a signal that has no name in Signal. It can only be created by
+/// Signal::from_raw().
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OtherSignal(i32);
+
+impl OtherSignal {
+    /// The platform's number for the signal.
+    pub fn as_raw(self) -> i32 {
+        self.0
+    }
+}
+
+macro_rules! signals {
+    (
+        all: $($all:ident),*;
+        unix: $($unix:ident),*;
+        windows: $($windows:ident),*;
+    ) => {
+        /// A signal, for use with SignalHandle, SignalSet, ProcessHandle::kill() and friends.
+        ///
+        /// Signals are converted to the platform's signal numbers. Signals that are not listed,
+        /// such as realtime signals (SIGRTMIN+n), are represented by Signal::Other, which is
+        /// created with Signal::from_raw(). Functions that take a signal accept anything that is
+        /// Into<Signal>, which includes raw i32 signal numbers.
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        pub enum Signal {
+            $($all,)*
+            $(#[cfg(unix)] $unix,)*
+            $(#[cfg(windows)] $windows,)*
+
+            /// Any other signal number. This never holds the number of a named signal, so
+            /// signals compare equal if and only if their numbers are equal.
+            Other(OtherSignal),
+        }
+
+        impl Signal {
+            /// The platform's number for the signal.
+            pub fn as_raw(self) -> i32 {
+                match self {
+                    $(Signal::$all => uv::$all as _,)*
+                    $(#[cfg(unix)] Signal::$unix => uv::$unix as _,)*
+                    $(#[cfg(windows)] Signal::$windows => uv::$windows as _,)*
+                    Signal::Other(other) => other.0,
+                }
+            }
+
+            /// The signal with the given number. Numbers that do not match a named signal become
+            /// Signal::Other.
+            pub fn from_raw(signum: i32) -> Signal {
+                $(if signum == uv::$all as i32 {
+                    return Signal::$all;
+                })*
+                $(#[cfg(unix)] {
+                    if signum == uv::$unix as i32 {
+                        return Signal::$unix;
+                    }
+                })*
+                $(#[cfg(windows)] {
+                    if signum == uv::$windows as i32 {
+                        return Signal::$windows;
+                    }
+                })*
+                Signal::Other(OtherSignal(signum))
+            }
+
+            /// The name of the signal, such as "SIGTERM", or None for Signal::Other.
+            pub fn name(self) -> Option<&'static str> {
+                match self {
+                    $(Signal::$all => Some(stringify!($all)),)*
+                    $(#[cfg(unix)] Signal::$unix => Some(stringify!($unix)),)*
+                    $(#[cfg(windows)] Signal::$windows => Some(stringify!($windows)),)*
+                    Signal::Other(_) => None,
+                }
+            }
+
+            /// The signal with the given name, such as "SIGTERM".
+            fn from_name(name: &str) -> Option<Signal> {
+                match name {
+                    $(stringify!($all) => Some(Signal::$all),)*
+                    $(#[cfg(unix)] stringify!($unix) => Some(Signal::$unix),)*
+                    $(#[cfg(windows)] stringify!($windows) => Some(Signal::$windows),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+signals! {
+    all: SIGHUP, SIGINT, SIGILL, SIGABRT, SIGFPE, SIGKILL, SIGSEGV, SIGTERM, SIGWINCH;
+    unix: SIGQUIT, SIGTRAP, SIGBUS, SIGUSR1, SIGUSR2, SIGPIPE, SIGALRM, SIGCHLD, SIGCONT, SIGSTOP,
+        SIGTSTP, SIGTTIN, SIGTTOU, SIGURG, SIGXCPU, SIGXFSZ, SIGVTALRM, SIGPROF, SIGIO, SIGSYS;
+    windows: SIGBREAK;
+}
+
+impl From<i32> for Signal {
+    fn from(signum: i32) -> Signal {
+        Signal::from_raw(signum)
+    }
+}
+
+impl From<Signal> for i32 {
+    fn from(signal: Signal) -> i32 {
+        signal.as_raw()
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{}", self.as_raw()),
+        }
+    }
+}
+
+impl FromStr for Signal {
+    type Err = crate::Error;
+
+    /// Parses a signal name, such as "SIGTERM". The "SIG" prefix is optional and case is ignored,
+    /// so "term" also works. A signal number is accepted too.
+    fn from_str(s: &str) -> Result<Signal, Self::Err> {
+        if let Ok(signum) = s.parse::<i32>() {
+            return Ok(Signal::from_raw(signum));
+        }
+
+        let name = s.to_ascii_uppercase();
+        let name = if name.starts_with("SIG") {
+            name
+        } else {
+            format!("SIG{}", name)
+        };
+        Signal::from_name(&name).ok_or(crate::Error::EINVAL)
+    }
+}
@@ -0,0 +1,560 @@
+use crate::{HandleTrait, StreamTrait};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+bitflags! {
+    /// Modifier keys that were held down during a key press or mouse event.
+    pub struct KeyModifiers: u8 {
+        const SHIFT = 1;
+        const ALT = 2;
+        const CTRL = 4;
+    }
+}
+
+impl Default for KeyModifiers {
+    fn default() -> Self {
+        KeyModifiers::empty()
+    }
+}
+
+/// A key on the keyboard.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    /// A character. Shift is not reported for characters: Shift+a is simply 'A'. For Ctrl+letter,
+    /// the character is the lowercase letter.
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+
+    /// A function key, F1 through F12.
+    F(u8),
+}
+
+/// A key press.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: KeyModifiers,
+}
+
+/// The button involved in a mouse event.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+
+    /// No button, or the terminal did not say which button was released.
+    None,
+}
+
+/// What happened in a mouse event.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MouseAction {
+    /// A button was pressed, or the wheel was scrolled.
+    Press,
+    Release,
+
+    /// The mouse moved while a button was held down.
+    Drag,
+
+    /// The mouse moved without any buttons held down. Only reported if the terminal was asked to
+    /// report all motion.
+    Move,
+}
+
+/// A mouse report. column and row are zero-based.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MouseEvent {
+    pub action: MouseAction,
+    pub button: MouseButton,
+    pub column: u16,
+    pub row: u16,
+    pub modifiers: KeyModifiers,
+}
+
+/// An event decoded from terminal input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+
+    /// Text that was pasted while bracketed paste was enabled.
+    Paste(String),
+}
+
+/// Write to the terminal to turn bracketed paste on. Pasted text is then reported as a single
+/// InputEvent::Paste, rather than as key presses.
+pub const BRACKETED_PASTE_ON: &[u8] = b"\x1b[?2004h";
+
+/// Write to the terminal to turn bracketed paste off.
+pub const BRACKETED_PASTE_OFF: &[u8] = b"\x1b[?2004l";
+
+/// Write to the terminal to turn on reporting of mouse button presses, releases and drags, using
+/// SGR extended coordinates. The KeyDecoder must also have mouse reports enabled.
+pub const MOUSE_REPORTING_ON: &[u8] = b"\x1b[?1002h\x1b[?1006h";
+
+/// Write to the terminal to turn mouse reporting off.
+pub const MOUSE_REPORTING_OFF: &[u8] = b"\x1b[?1006l\x1b[?1002l";
+
+/// Marks the end of bracketed paste.
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Escape sequences longer than this are assumed to be garbage and dropped.
+const MAX_SEQUENCE_LEN: usize = 64;
+
+/// How long read_keys() waits for the rest of an escape sequence before deciding that ESC was
+/// pressed on its own, in milliseconds.
+const ESCAPE_TIMEOUT: u64 = 50;
+
+/// The result of parsing the start of the input.
+enum Parsed {
+    /// An event, and the number of bytes it used.
+    Event(InputEvent, usize),
+
+    /// A sequence that is not understood, and its length.
+    Skip(usize),
+
+    /// The start of bracketed paste, and its length.
+    PasteStart(usize),
+
+    /// More input is needed.
+    Incomplete,
+}
+
+fn key_event(key: Key, modifiers: KeyModifiers) -> InputEvent {
+    InputEvent::Key(KeyEvent { key, modifiers })
+}
+
+/// Parse the numeric parameters of a CSI sequence. Missing parameters are 0.
+fn parse_params(params: &[u8]) -> Vec<u16> {
+    params
+        .split(|b| *b == b';')
+        .map(|param| {
+            std::str::from_utf8(param)
+                .ok()
+                .and_then(|param| param.parse().ok())
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Decode the modifier parameter of a CSI sequence, which is 1 plus a bitmask of shift (1), alt
+/// (2) and ctrl (4). Meta (8) is reported as alt.
+fn modifiers_param(param: Option<&u16>) -> KeyModifiers {
+    match param {
+        Some(&param) if param > 1 => {
+            let bits = param - 1;
+            let mut modifiers = KeyModifiers::from_bits_truncate((bits & 7) as u8);
+            if bits & 8 != 0 {
+                modifiers |= KeyModifiers::ALT;
+            }
+            modifiers
+        }
+        _ => KeyModifiers::empty(),
+    }
+}
+
+/// Decode a mouse report. code is the button code from the terminal, and column and row are
+/// one-based.
+fn mouse_event(code: u16, column: u16, row: u16, release: bool) -> InputEvent {
+    let mut modifiers = KeyModifiers::empty();
+    if code & 4 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if code & 8 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if code & 16 != 0 {
+        modifiers |= KeyModifiers::CTRL;
+    }
+
+    let wheel = code & 64 != 0;
+    let motion = code & 32 != 0;
+    let button = match (code & 3, wheel) {
+        (0, true) => MouseButton::WheelUp,
+        (1, true) => MouseButton::WheelDown,
+        (0, false) => MouseButton::Left,
+        (1, false) => MouseButton::Middle,
+        (2, false) => MouseButton::Right,
+        _ => MouseButton::None,
+    };
+    let action = if wheel {
+        MouseAction::Press
+    } else if motion {
+        if button == MouseButton::None {
+            MouseAction::Move
+        } else {
+            MouseAction::Drag
+        }
+    } else if release || button == MouseButton::None {
+        // in the original X10 encoding, a release is reported as button 3
+        MouseAction::Release
+    } else {
+        MouseAction::Press
+    };
+
+    InputEvent::Mouse(MouseEvent {
+        action,
+        button,
+        column: column.saturating_sub(1),
+        row: row.saturating_sub(1),
+        modifiers,
+    })
+}
+
+/// Parse a single character or control code.
+fn parse_char(input: &[u8]) -> Parsed {
+    let b = input[0];
+    let (key, modifiers) = match b {
+        b'\r' | b'\n' => (Key::Enter, KeyModifiers::empty()),
+        b'\t' => (Key::Tab, KeyModifiers::empty()),
+        0x08 | 0x7f => (Key::Backspace, KeyModifiers::empty()),
+        0x00 => (Key::Char(' '), KeyModifiers::CTRL),
+        0x01..=0x1a => (Key::Char((b - 1 + b'a') as char), KeyModifiers::CTRL),
+        0x1c..=0x1f => (
+            Key::Char(b"\\]^_"[(b - 0x1c) as usize] as char),
+            KeyModifiers::CTRL,
+        ),
+        _ => {
+            let len = match b {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return Parsed::Skip(1),
+            };
+            if input.len() < len {
+                return Parsed::Incomplete;
+            }
+            return match std::str::from_utf8(&input[..len]) {
+                Ok(s) => match s.chars().next() {
+                    Some(c) => Parsed::Event(key_event(Key::Char(c), KeyModifiers::empty()), len),
+                    None => Parsed::Skip(len),
+                },
+                Err(_) => Parsed::Skip(1),
+            };
+        }
+    };
+    Parsed::Event(key_event(key, modifiers), 1)
+}
+
+/// Parse a CSI sequence: ESC [ parameters final.
+fn parse_csi(input: &[u8], mouse: bool) -> Parsed {
+    // X10 mouse report: ESC [ M followed by three bytes
+    if input.get(2) == Some(&b'M') {
+        if input.len() < 6 {
+            return Parsed::Incomplete;
+        }
+        if !mouse {
+            return Parsed::Skip(6);
+        }
+        let code = (input[3] as u16).saturating_sub(32);
+        let column = (input[4] as u16).saturating_sub(32);
+        let row = (input[5] as u16).saturating_sub(32);
+        return Parsed::Event(mouse_event(code, column, row, false), 6);
+    }
+
+    let mut end = 2;
+    loop {
+        match input.get(end) {
+            None if end > MAX_SEQUENCE_LEN => return Parsed::Skip(end),
+            None => return Parsed::Incomplete,
+            Some(0x40..=0x7e) => break,
+            Some(0x20..=0x3f) => end += 1,
+            Some(_) => return Parsed::Skip(end),
+        }
+    }
+    let params = &input[2..end];
+    let fin = input[end];
+    let len = end + 1;
+
+    // SGR mouse report: ESC [ < code ; column ; row M (or m for a release)
+    if params.first() == Some(&b'<') {
+        if !mouse || (fin != b'M' && fin != b'm') {
+            return Parsed::Skip(len);
+        }
+        let params = parse_params(&params[1..]);
+        if params.len() != 3 {
+            return Parsed::Skip(len);
+        }
+        return Parsed::Event(
+            mouse_event(params[0], params[1], params[2], fin == b'm'),
+            len,
+        );
+    }
+
+    let params = parse_params(params);
+    let modifiers = modifiers_param(params.get(1));
+    let key = match fin {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P'..=b'S' => Key::F(fin - b'P' + 1),
+        b'Z' => return Parsed::Event(key_event(Key::Tab, KeyModifiers::SHIFT), len),
+        b'~' => match params.first().copied().unwrap_or(0) {
+            1 | 7 => Key::Home,
+            2 => Key::Insert,
+            3 => Key::Delete,
+            4 | 8 => Key::End,
+            5 => Key::PageUp,
+            6 => Key::PageDown,
+            n @ 11..=15 => Key::F((n - 10) as u8),
+            n @ 17..=21 => Key::F((n - 11) as u8),
+            n @ 23..=24 => Key::F((n - 12) as u8),
+            200 => return Parsed::PasteStart(len),
+            _ => return Parsed::Skip(len),
+        },
+        _ => return Parsed::Skip(len),
+    };
+    Parsed::Event(key_event(key, modifiers), len)
+}
+
+/// Parse an escape sequence, or ESC followed by a key, which is how terminals report Alt+key.
+fn parse_escape(input: &[u8], mouse: bool) -> Parsed {
+    match input.get(1) {
+        None => Parsed::Incomplete,
+        Some(b'[') => parse_csi(input, mouse),
+        Some(b'O') => {
+            // SS3 sequences, sent by some terminals for arrows and F1-F4
+            let key = match input.get(2) {
+                None => return Parsed::Incomplete,
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(b @ b'P'..=b'S') => Key::F(b - b'P' + 1),
+                Some(_) => return Parsed::Skip(3),
+            };
+            Parsed::Event(key_event(key, KeyModifiers::empty()), 3)
+        }
+        Some(0x1b) => Parsed::Event(key_event(Key::Escape, KeyModifiers::empty()), 1),
+        Some(_) => match parse_char(&input[1..]) {
+            Parsed::Event(InputEvent::Key(mut event), len) => {
+                event.modifiers |= KeyModifiers::ALT;
+                Parsed::Event(InputEvent::Key(event), len + 1)
+            }
+            Parsed::Incomplete => Parsed::Incomplete,
+            _ => Parsed::Skip(2),
+        },
+    }
+}
+
+/// Decodes the bytes read from a terminal in raw mode into key presses, pastes and mouse reports.
+///
+/// Input is passed to feed() as it arrives, and may be split anywhere: incomplete escape sequences
+/// and characters are kept until the rest arrives. Since the Escape key sends the same byte that
+/// starts an escape sequence, a lone ESC is not reported until more input arrives or flush() is
+/// called - read_keys() does that after a short timeout.
+#[derive(Default)]
+pub struct KeyDecoder {
+    input: Vec<u8>,
+    paste: Option<Vec<u8>>,
+    mouse: bool,
+}
+
+impl KeyDecoder {
+    /// Create a new decoder. Mouse reports are not decoded.
+    pub fn new() -> KeyDecoder {
+        Default::default()
+    }
+
+    /// Decode mouse reports. Terminals only send them once asked to, with MOUSE_REPORTING_ON.
+    pub fn mouse(mut self, enable: bool) -> KeyDecoder {
+        self.mouse = enable;
+        self
+    }
+
+    /// Returns true if the decoder is holding the start of an escape sequence or character.
+    pub fn is_pending(&self) -> bool {
+        self.paste.is_none() && !self.input.is_empty()
+    }
+
+    /// Decode more input.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<InputEvent> {
+        self.input.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        self.decode(&mut events, false);
+        events
+    }
+
+    /// Decode any pending input that is waiting for the rest of an escape sequence: a lone ESC is
+    /// reported as the Escape key.
+    pub fn flush(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        self.decode(&mut events, true);
+        events
+    }
+
+    fn decode(&mut self, events: &mut Vec<InputEvent>, flush: bool) {
+        let mut pos = 0;
+        loop {
+            if let Some(paste) = &mut self.paste {
+                let rest = &self.input[pos..];
+                match rest.windows(PASTE_END.len()).position(|w| w == PASTE_END) {
+                    Some(end) => {
+                        paste.extend_from_slice(&rest[..end]);
+                        pos += end + PASTE_END.len();
+                        let text = String::from_utf8_lossy(paste).into_owned();
+                        self.paste = None;
+                        events.push(InputEvent::Paste(text));
+                        continue;
+                    }
+                    None => {
+                        // keep anything that could be the start of the end marker
+                        let keep = (1..PASTE_END.len().min(rest.len() + 1))
+                            .rev()
+                            .find(|len| rest.ends_with(&PASTE_END[..*len]))
+                            .unwrap_or(0);
+                        paste.extend_from_slice(&rest[..rest.len() - keep]);
+                        pos = self.input.len() - keep;
+                        break;
+                    }
+                }
+            }
+
+            if pos >= self.input.len() {
+                break;
+            }
+            let input = &self.input[pos..];
+            let parsed = if input[0] == 0x1b {
+                parse_escape(input, self.mouse)
+            } else {
+                parse_char(input)
+            };
+            match parsed {
+                Parsed::Event(event, len) => {
+                    events.push(event);
+                    pos += len;
+                }
+                Parsed::Skip(len) => pos += len,
+                Parsed::PasteStart(len) => {
+                    self.paste = Some(Vec::new());
+                    pos += len;
+                }
+                Parsed::Incomplete if flush => {
+                    // a lone ESC, or the start of a sequence that never finished
+                    if input[0] == 0x1b {
+                        events.push(key_event(Key::Escape, KeyModifiers::empty()));
+                    }
+                    pos += 1;
+                }
+                Parsed::Incomplete => break,
+            }
+        }
+        self.input.drain(..pos);
+    }
+}
+
+callbacks! {
+    pub InputEventCB(tty: crate::TtyHandle, event: crate::Result<InputEvent>);
+}
+
+/// The state of a read_keys() call.
+struct KeyReader {
+    decoder: KeyDecoder,
+    cb: InputEventCB<'static>,
+}
+
+type State = Rc<RefCell<KeyReader>>;
+
+/// The timer that flushes a lone ESC. It is owned by the read callback, so it is closed when the
+/// TTY is closed or reading is restarted with other callbacks.
+struct EscapeTimer(crate::TimerHandle);
+
+impl Drop for EscapeTimer {
+    fn drop(&mut self) {
+        if !self.0.is_closing() {
+            self.0.close(());
+        }
+    }
+}
+
+/// Call the callback for each event. The callback is taken out of the state while it runs.
+fn deliver(
+    tty: crate::TtyHandle,
+    state: &State,
+    events: impl IntoIterator<Item = crate::Result<InputEvent>>,
+) {
+    let mut cb = std::mem::take(&mut state.borrow_mut().cb);
+    for event in events {
+        cb.call(tty, event);
+    }
+    let mut s = state.borrow_mut();
+    s.cb.restore(cb);
+}
+
+/// Flush the decoder if no more input arrives within ESCAPE_TIMEOUT.
+fn arm_escape_timer(mut timer: crate::TimerHandle, tty: crate::TtyHandle, state: &State) {
+    let state = state.clone();
+    let _ = timer.start(ESCAPE_TIMEOUT, 0, move |_: crate::TimerHandle| {
+        let events = state.borrow_mut().decoder.flush();
+        deliver(tty, &state, events.into_iter().map(Ok));
+    });
+}
+
+impl crate::TtyHandle {
+    /// Start reading from the TTY, decoding the input with the given decoder. The callback is
+    /// called once for each key press, paste or mouse report. Read errors, including EOF, are
+    /// passed to the callback as well.
+    ///
+    /// The TTY should be in raw mode, see raw_mode(). Use read_stop() to stop reading.
+    pub fn read_keys<CB: Into<InputEventCB<'static>>>(
+        &mut self,
+        decoder: KeyDecoder,
+        cb: CB,
+    ) -> crate::Result<()> {
+        let tty = *self;
+        let escape_timer = EscapeTimer(self.get_loop().timer()?);
+        let state = Rc::new(RefCell::new(KeyReader {
+            decoder,
+            cb: cb.into(),
+        }));
+
+        self.read_start(
+            |_, size| crate::Buf::with_capacity(size).ok(),
+            move |_, nread: crate::Result<usize>, mut buf: crate::ReadonlyBuf| {
+                let mut timer = escape_timer.0;
+                match nread {
+                    Ok(0) => {}
+                    Ok(len) => {
+                        let _ = timer.stop();
+                        let (events, pending) = {
+                            let mut s = state.borrow_mut();
+                            let events = s.decoder.feed(&buf[..len]);
+                            (events, s.decoder.is_pending())
+                        };
+                        if pending {
+                            arm_escape_timer(timer, tty, &state);
+                        }
+                        deliver(tty, &state, events.into_iter().map(Ok));
+                    }
+                    Err(e) => {
+                        let _ = timer.stop();
+                        deliver(tty, &state, std::iter::once(Err(e)));
+                    }
+                }
+                buf.dealloc();
+            },
+        )
+    }
+}
@@ -0,0 +1,727 @@
+use crate::{
+    FsModeFlags, FsOpenFlags, HandleTrait, InputEvent, Key, KeyDecoder, KeyModifiers, RawModeGuard,
+    StreamTrait, TtyHandle, TtyMode,
+};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+callbacks! {
+    pub LineCB(editor: LineEditor, line: crate::Result<String>);
+    pub CompleteCB(line: &str, pos: usize) -> (usize, Vec<String>);
+}
+
+/// What to do once the state is no longer borrowed.
+enum Action {
+    None,
+    Accept(String),
+    Cancel,
+    Eof,
+    Complete,
+}
+
+struct EditorState {
+    input: TtyHandle,
+    output: TtyHandle,
+    prompt: String,
+    line: Vec<char>,
+    cursor: usize,
+    scroll: usize,
+    history: VecDeque<String>,
+    history_size: usize,
+    history_pos: Option<usize>,
+    saved_line: Vec<char>,
+    history_file: Option<String>,
+    history_loaded: bool,
+    history_writes: VecDeque<String>,
+    history_writing: bool,
+    line_cb: LineCB<'static>,
+    complete_cb: CompleteCB<'static>,
+    last_was_tab: bool,
+    raw_mode: Option<RawModeGuard>,
+}
+
+type State = Rc<RefCell<EditorState>>;
+
+/// The number of columns that text takes up on the terminal, ignoring escape sequences (such as
+/// colors) in the text.
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip ESC [ ... final
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else if !c.is_control() {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Write text to the terminal. The text is copied, so it can be dropped as soon as this returns.
+fn write_out(mut output: TtyHandle, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let _ = crate::write_copy(&mut output, text.as_bytes(), |_| {});
+}
+
+impl EditorState {
+    fn line_string(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// The escape sequences that redraw the prompt and line, and place the cursor. Lines that are
+    /// too wide for the terminal are scrolled horizontally to keep the cursor visible.
+    fn render(&mut self) -> String {
+        let prompt_width = display_width(&self.prompt);
+        let cols = match self.output.get_winsize() {
+            Ok((cols, _)) if cols > 0 => cols as usize,
+            _ => 80,
+        };
+        let avail = cols.saturating_sub(prompt_width + 1).max(1);
+        if self.line.len() <= avail {
+            self.scroll = 0;
+        } else if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor - self.scroll > avail {
+            self.scroll = self.cursor - avail;
+        }
+
+        let end = (self.scroll + avail).min(self.line.len());
+        let visible: String = self.line[self.scroll..end].iter().collect();
+        let mut out = format!("\r{}{}\x1b[K\r", self.prompt, visible);
+        let column = prompt_width + self.cursor - self.scroll;
+        if column > 0 {
+            out.push_str(&format!("\x1b[{}C", column));
+        }
+        out
+    }
+
+    fn redraw(&mut self) {
+        let out = self.render();
+        write_out(self.output, &out);
+    }
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    fn insert(&mut self, text: impl Iterator<Item = char>) {
+        for c in text {
+            self.line.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    /// The position of the start of the word before the cursor.
+    fn word_left(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos > 0 && self.line[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        while pos > 0 && !self.line[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// The position of the end of the word after the cursor.
+    fn word_right(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos < self.line.len() && self.line[pos].is_whitespace() {
+            pos += 1;
+        }
+        while pos < self.line.len() && !self.line[pos].is_whitespace() {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn history_prev(&mut self) {
+        let pos = match self.history_pos {
+            _ if self.history.is_empty() => return,
+            None => {
+                self.saved_line = std::mem::take(&mut self.line);
+                self.history.len() - 1
+            }
+            Some(0) => return,
+            Some(pos) => pos - 1,
+        };
+        self.history_pos = Some(pos);
+        self.set_line(self.history[pos].chars().collect());
+    }
+
+    fn history_next(&mut self) {
+        match self.history_pos {
+            None => {}
+            Some(pos) if pos + 1 < self.history.len() => {
+                self.history_pos = Some(pos + 1);
+                self.set_line(self.history[pos + 1].chars().collect());
+            }
+            Some(_) => {
+                self.history_pos = None;
+                let line = std::mem::take(&mut self.saved_line);
+                self.set_line(line);
+            }
+        }
+    }
+
+    /// Handle a key press or paste. Anything that calls back into user code is returned as an
+    /// Action, to be done once the state is no longer borrowed.
+    fn handle(&mut self, event: InputEvent) -> Action {
+        let key = match event {
+            InputEvent::Key(key) => key,
+            InputEvent::Paste(text) => {
+                let text = text.chars().filter_map(|c| match c {
+                    '\n' | '\r' | '\t' => Some(' '),
+                    c if c.is_control() => None,
+                    c => Some(c),
+                });
+                self.insert(text);
+                self.last_was_tab = false;
+                self.redraw();
+                return Action::None;
+            }
+            InputEvent::Mouse(_) => return Action::None,
+        };
+
+        let was_tab = std::mem::replace(&mut self.last_was_tab, false);
+        let ctrl = key.modifiers.contains(KeyModifiers::CTRL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.key {
+            Key::Char(c) if ctrl => match c {
+                'a' => self.cursor = 0,
+                'e' => self.cursor = self.line.len(),
+                'b' => self.cursor = self.cursor.saturating_sub(1),
+                'f' => self.cursor = (self.cursor + 1).min(self.line.len()),
+                'h' if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+                'd' if self.line.is_empty() => return Action::Eof,
+                'd' if self.cursor < self.line.len() => {
+                    self.line.remove(self.cursor);
+                }
+                'c' => return Action::Cancel,
+                'u' => {
+                    self.line.drain(..self.cursor);
+                    self.cursor = 0;
+                }
+                'k' => self.line.truncate(self.cursor),
+                'w' => {
+                    let start = self.word_left();
+                    self.line.drain(start..self.cursor);
+                    self.cursor = start;
+                }
+                'l' => write_out(self.output, "\x1b[2J\x1b[H"),
+                'p' => self.history_prev(),
+                'n' => self.history_next(),
+                _ => return Action::None,
+            },
+            Key::Char(c) if alt => match c {
+                'b' => self.cursor = self.word_left(),
+                'f' => self.cursor = self.word_right(),
+                'd' => {
+                    let end = self.word_right();
+                    self.line.drain(self.cursor..end);
+                }
+                _ => return Action::None,
+            },
+            Key::Char(c) => self.insert(std::iter::once(c)),
+            Key::Enter => {
+                let line = self.line_string();
+                self.line.clear();
+                self.cursor = 0;
+                self.scroll = 0;
+                self.history_pos = None;
+                self.saved_line.clear();
+                write_out(self.output, "\r\n");
+                return Action::Accept(line);
+            }
+            Key::Tab if key.modifiers.is_empty() => {
+                self.last_was_tab = was_tab;
+                return Action::Complete;
+            }
+            Key::Backspace if alt => {
+                let start = self.word_left();
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            Key::Left if ctrl || alt => self.cursor = self.word_left(),
+            Key::Right if ctrl || alt => self.cursor = self.word_right(),
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.line.len(),
+            Key::Up => self.history_prev(),
+            Key::Down => self.history_next(),
+            _ => return Action::None,
+        }
+        self.redraw();
+        Action::None
+    }
+
+    /// Apply the candidates returned by the completion callback for the word that starts at start
+    /// (a byte offset into line).
+    fn apply_completion(&mut self, line: &str, pos: usize, start: usize, candidates: Vec<String>) {
+        let listing = std::mem::replace(&mut self.last_was_tab, true);
+        let valid = start <= pos && line.is_char_boundary(start);
+        if !valid || candidates.is_empty() {
+            write_out(self.output, "\x07");
+            return;
+        }
+
+        // the longest prefix that all of the candidates share
+        let mut prefix: Vec<char> = candidates[0].chars().collect();
+        for candidate in &candidates[1..] {
+            let len = prefix
+                .iter()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| **a == *b)
+                .count();
+            prefix.truncate(len);
+        }
+
+        let word_start = line[..start].chars().count();
+        let word_len = self.cursor - word_start;
+        if prefix.len() > word_len {
+            self.line
+                .splice(word_start..self.cursor, prefix.iter().copied());
+            self.cursor = word_start + prefix.len();
+            self.redraw();
+        } else if candidates.len() > 1 && listing {
+            // a second tab lists the candidates
+            let list = format!("\r\n{}\r\n", candidates.join("  "));
+            let out = list + &self.render();
+            write_out(self.output, &out);
+        } else {
+            write_out(self.output, "\x07");
+        }
+    }
+}
+
+/// Read the history file, and add its lines before the history that has been entered since.
+fn load_history(r#loop: &crate::Loop, state: &State, path: &str) {
+    let state = state.clone();
+    let result = r#loop.fs_open(
+        path,
+        FsOpenFlags::RDONLY,
+        FsModeFlags::empty(),
+        move |req: crate::FsReq| {
+            if let Ok(file) = req.result() {
+                read_history(req.r#loop(), state.clone(), file as _, Vec::new());
+            }
+        },
+    );
+    // a missing history file is not an error
+    let _ = result;
+}
+
+fn read_history(r#loop: crate::Loop, state: State, file: crate::File, mut data: Vec<u8>) {
+    let buf = match crate::Buf::with_capacity(4096) {
+        Ok(buf) => buf,
+        Err(_) => return,
+    };
+    let result = r#loop.fs_read(file, &[buf], -1, move |req: crate::FsReq| {
+        let mut buf = buf;
+        match req.result() {
+            Ok(0) | Err(_) => {
+                buf.destroy();
+                let _ = req.r#loop().fs_close(file, ());
+
+                let text = String::from_utf8_lossy(&data).into_owned();
+                let mut s = state.borrow_mut();
+                let entered = std::mem::take(&mut s.history);
+                s.history = text
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_owned())
+                    .chain(entered)
+                    .collect();
+                while s.history.len() > s.history_size {
+                    s.history.pop_front();
+                }
+            }
+            Ok(len) => {
+                data.extend_from_slice(&crate::ReadonlyBuf::from(buf)[..len]);
+                buf.destroy();
+                read_history(req.r#loop(), state.clone(), file, std::mem::take(&mut data));
+            }
+        }
+    });
+    if result.is_err() {
+        let mut buf = buf;
+        buf.destroy();
+        let _ = r#loop.fs_close(file, ());
+    }
+}
+
+/// Append the next queued line to the history file. Lines are written one at a time so that they
+/// stay in order.
+fn write_history(r#loop: &crate::Loop, state: &State) {
+    let (path, line) = {
+        let mut s = state.borrow_mut();
+        if s.history_writing {
+            return;
+        }
+        let path = match &s.history_file {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        match s.history_writes.pop_front() {
+            Some(line) => {
+                s.history_writing = true;
+                (path, line)
+            }
+            None => return,
+        }
+    };
+
+    let done = |r#loop: &crate::Loop, state: &State| {
+        state.borrow_mut().history_writing = false;
+        write_history(r#loop, state);
+    };
+
+    let open_state = state.clone();
+    let result = r#loop.fs_open(
+        &path,
+        FsOpenFlags::WRONLY | FsOpenFlags::APPEND | FsOpenFlags::CREAT,
+        FsModeFlags::OWNER_READ | FsModeFlags::OWNER_WRITE,
+        move |req: crate::FsReq| {
+            let r#loop = req.r#loop();
+            let file = match req.result() {
+                Ok(file) => file as crate::File,
+                Err(_) => return done(&r#loop, &open_state),
+            };
+            let buf = match crate::Buf::from_slice(format!("{}\n", line).as_bytes()) {
+                Ok(buf) => buf,
+                Err(_) => {
+                    let _ = r#loop.fs_close(file, ());
+                    return done(&r#loop, &open_state);
+                }
+            };
+            let write_state = open_state.clone();
+            let result = r#loop.fs_write(file, &[buf], -1, move |req: crate::FsReq| {
+                let mut buf = buf;
+                buf.destroy();
+                let r#loop = req.r#loop();
+                let _ = r#loop.fs_close(file, ());
+                done(&r#loop, &write_state);
+            });
+            if result.is_err() {
+                let mut buf = buf;
+                buf.destroy();
+                let _ = r#loop.fs_close(file, ());
+                done(&r#loop, &open_state);
+            }
+        },
+    );
+    if result.is_err() {
+        done(r#loop, state);
+    }
+}
+
+/// A readline-style line editor for a TTY.
+///
+/// The editor puts the input TTY into raw mode and reads from it on the loop, so timers and other
+/// handles keep running while the user types. Completed lines are passed to a callback. It
+/// supports:
+///
+/// * moving the cursor with the arrow keys, Home/End, Ctrl+A/E/B/F, and by word with
+///   Ctrl+Left/Right or Alt+B/F
+/// * deleting with Backspace/Delete, Ctrl+U/K (to the start/end of the line), and by word with
+///   Ctrl+W, Alt+Backspace or Alt+D
+/// * history with Up/Down or Ctrl+P/N, optionally saved to a file
+/// * tab completion through a callback
+/// * Ctrl+L to clear the screen, Ctrl+C to discard the line, and Ctrl+D on an empty line for EOF
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::LineEditor;
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let input = r#loop.tty(0).unwrap();
+/// let output = r#loop.tty(1).unwrap();
+/// let editor = LineEditor::new(&input, &output).prompt("> ");
+/// editor
+///     .start(move |editor: LineEditor, line: libuv::Result<String>| match line {
+///         Ok(line) => editor.print(&format!("you typed: {}", line)),
+///         Err(_) => editor.stop(),
+///     })
+///     .unwrap();
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct LineEditor {
+    state: State,
+}
+
+impl LineEditor {
+    /// Create a new line editor that reads from input and draws on output. These are usually TTYs
+    /// for stdin and stdout.
+    pub fn new(input: &TtyHandle, output: &TtyHandle) -> LineEditor {
+        LineEditor {
+            state: Rc::new(RefCell::new(EditorState {
+                input: *input,
+                output: *output,
+                prompt: String::new(),
+                line: Vec::new(),
+                cursor: 0,
+                scroll: 0,
+                history: VecDeque::new(),
+                history_size: 1000,
+                history_pos: None,
+                saved_line: Vec::new(),
+                history_file: None,
+                history_loaded: false,
+                history_writes: VecDeque::new(),
+                history_writing: false,
+                line_cb: ().into(),
+                complete_cb: ().into(),
+                last_was_tab: false,
+                raw_mode: None,
+            })),
+        }
+    }
+
+    /// Set the prompt. It may contain escape sequences, such as colors.
+    pub fn prompt(self, prompt: &str) -> LineEditor {
+        self.set_prompt(prompt);
+        self
+    }
+
+    /// Set the maximum number of lines to keep in the history. The default is 1000.
+    pub fn history_size(self, size: usize) -> LineEditor {
+        {
+            let mut state = self.state.borrow_mut();
+            state.history_size = size;
+            while state.history.len() > size {
+                state.history.pop_front();
+            }
+        }
+        self
+    }
+
+    /// Persist the history in a file. The file is read when the editor is first started, and each
+    /// line that is added to the history is appended to it.
+    pub fn history_file(self, path: &str) -> LineEditor {
+        self.state.borrow_mut().history_file = Some(path.to_owned());
+        self
+    }
+
+    /// Set the callback for tab completion. It receives the line and the cursor position (a byte
+    /// offset), and returns the byte offset where the word being completed starts, along with the
+    /// possible completions of that word.
+    ///
+    /// If there is a single completion, the word is replaced with it. If there are several, the
+    /// word is extended with their common prefix, and pressing tab again lists them.
+    pub fn on_complete<CB: Into<CompleteCB<'static>>>(self, cb: CB) -> LineEditor {
+        self.state.borrow_mut().complete_cb = cb.into();
+        self
+    }
+
+    /// Put the input into raw mode, show the prompt, and start reading lines. Each line is passed
+    /// to the callback once Enter is pressed, and added to the history. Ctrl+C discards the line
+    /// and passes ECANCELED to the callback. Ctrl+D on an empty line, or a read error, stops the
+    /// editor and passes EOF or the error to the callback.
+    ///
+    /// Pasted text is inserted into the line, with newlines replaced by spaces.
+    pub fn start<CB: Into<LineCB<'static>>>(&self, cb: CB) -> crate::Result<()> {
+        let (mut input, history_file) = {
+            let mut state = self.state.borrow_mut();
+            if state.raw_mode.is_some() {
+                return Err(crate::Error::EALREADY);
+            }
+            state.raw_mode = Some(RawModeGuard::new(&state.input, TtyMode::Raw)?);
+            state.line_cb = cb.into();
+            let history_file = if state.history_loaded {
+                None
+            } else {
+                state.history_loaded = true;
+                state.history_file.clone()
+            };
+            (state.input, history_file)
+        };
+
+        let editor = self.clone();
+        let result = input.read_keys(
+            KeyDecoder::new(),
+            move |_, event: crate::Result<InputEvent>| editor.on_event(event),
+        );
+        if let Err(e) = result {
+            self.state.borrow_mut().raw_mode = None;
+            return Err(e);
+        }
+
+        if let Some(path) = history_file {
+            load_history(&input.get_loop(), &self.state, &path);
+        }
+
+        let mut state = self.state.borrow_mut();
+        let out = String::from_utf8_lossy(crate::BRACKETED_PASTE_ON).into_owned() + &state.render();
+        write_out(state.output, &out);
+        Ok(())
+    }
+
+    /// Stop reading and restore the terminal. The line that was being edited is kept, and is shown
+    /// again by start().
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.raw_mode.is_some() {
+            let _ = state.input.read_stop();
+            write_out(
+                state.output,
+                &String::from_utf8_lossy(crate::BRACKETED_PASTE_OFF),
+            );
+            state.raw_mode = None;
+        }
+    }
+
+    /// Returns true if the editor has been started, and has not stopped.
+    pub fn is_active(&self) -> bool {
+        self.state.borrow().raw_mode.is_some()
+    }
+
+    /// Change the prompt. If the editor is active, the line is redrawn.
+    pub fn set_prompt(&self, prompt: &str) {
+        let mut state = self.state.borrow_mut();
+        state.prompt = prompt.to_owned();
+        if state.raw_mode.is_some() {
+            state.redraw();
+        }
+    }
+
+    /// Print text without disturbing the line that is being edited: the text is written above it,
+    /// and the prompt and line are redrawn below.
+    pub fn print(&self, text: &str) {
+        let mut state = self.state.borrow_mut();
+        let mut out = text.replace("\r\n", "\n").replace('\n', "\r\n");
+        if state.raw_mode.is_some() {
+            if !out.ends_with("\r\n") {
+                out.push_str("\r\n");
+            }
+            out = format!("\r\x1b[K{}{}", out, state.render());
+        }
+        write_out(state.output, &out);
+    }
+
+    /// Add a line to the history. Empty lines, and lines that are the same as the last one, are
+    /// not added. If there is a history file, the line is appended to it.
+    pub fn add_history(&self, line: &str) {
+        let r#loop = {
+            let mut state = self.state.borrow_mut();
+            if line.is_empty() || state.history.back().map(|last| last.as_str()) == Some(line) {
+                return;
+            }
+            state.history.push_back(line.to_owned());
+            while state.history.len() > state.history_size {
+                state.history.pop_front();
+            }
+            if state.history_file.is_none() {
+                return;
+            }
+            state.history_writes.push_back(line.to_owned());
+            state.input.get_loop()
+        };
+        write_history(&r#loop, &self.state);
+    }
+
+    /// The lines in the history, oldest first.
+    pub fn history(&self) -> Vec<String> {
+        self.state.borrow().history.iter().cloned().collect()
+    }
+
+    /// Handle an event from the input.
+    fn on_event(&self, event: crate::Result<InputEvent>) {
+        let action = match event {
+            Ok(event) => self.state.borrow_mut().handle(event),
+            Err(e) => {
+                self.stop();
+                self.call_line_cb(Err(e));
+                return;
+            }
+        };
+
+        match action {
+            Action::None => {}
+            Action::Accept(line) => {
+                self.add_history(&line);
+                self.call_line_cb(Ok(line));
+                let mut state = self.state.borrow_mut();
+                if state.raw_mode.is_some() {
+                    state.redraw();
+                }
+            }
+            Action::Cancel => {
+                {
+                    let mut state = self.state.borrow_mut();
+                    state.line.clear();
+                    state.cursor = 0;
+                    state.history_pos = None;
+                    write_out(state.output, "^C\r\n");
+                }
+                self.call_line_cb(Err(crate::Error::ECANCELED));
+                let mut state = self.state.borrow_mut();
+                if state.raw_mode.is_some() {
+                    state.redraw();
+                }
+            }
+            Action::Eof => {
+                write_out(self.state.borrow().output, "\r\n");
+                self.stop();
+                self.call_line_cb(Err(crate::Error::EOF));
+            }
+            Action::Complete => self.complete(),
+        }
+    }
+
+    /// Call the line callback. The callback is taken out of the state while it runs, so that it
+    /// can use the editor.
+    fn call_line_cb(&self, line: crate::Result<String>) {
+        let mut cb = std::mem::take(&mut self.state.borrow_mut().line_cb);
+        cb.call(self.clone(), line);
+        let mut state = self.state.borrow_mut();
+        state.line_cb.restore(cb);
+    }
+
+    /// Ask the completion callback for completions of the word at the cursor.
+    fn complete(&self) {
+        let (mut cb, line, pos) = {
+            let mut state = self.state.borrow_mut();
+            if state.complete_cb.is_nil() {
+                // without completion, tab is inserted as a space
+                state.insert(std::iter::once(' '));
+                state.redraw();
+                return;
+            }
+            let pos = state.line[..state.cursor]
+                .iter()
+                .map(|c| c.len_utf8())
+                .sum();
+            let cb = std::mem::take(&mut state.complete_cb);
+            (cb, state.line_string(), pos)
+        };
+
+        let (start, candidates) = cb.call(&line, pos);
+
+        let mut state = self.state.borrow_mut();
+        state.complete_cb.restore(cb);
+        state.apply_completion(&line, pos, start, candidates);
+    }
+}
@@ -0,0 +1,14 @@
+pub mod keys;
+pub use keys::*;
+
+pub mod raw_mode;
+pub use raw_mode::*;
+
+pub mod resize;
+pub use resize::*;
+
+pub mod line_editor;
+pub use line_editor::*;
+
+pub mod styled;
+pub use styled::*;
@@ -0,0 +1,50 @@
+use crate::{TtyHandle, TtyMode};
+
+/// Puts a TTY into raw mode until the guard is dropped.
+///
+/// The terminal is reset when the guard goes out of scope, including on an early return or while
+/// a panic unwinds, so that the user is not left with an unusable terminal. It is reset with
+/// TtyHandle::reset_mode(), which restores the settings that were saved when a TTY first left
+/// normal mode. That means the guard does not depend on the TtyHandle still being open.
+pub struct RawModeGuard {
+    tty: TtyHandle,
+    restored: bool,
+}
+
+impl RawModeGuard {
+    /// Set the TTY to the given mode, which would usually be TtyMode::Raw or TtyMode::RawVT.
+    pub fn new(tty: &TtyHandle, mode: TtyMode) -> crate::Result<RawModeGuard> {
+        let mut tty = *tty;
+        tty.set_mode(mode)?;
+        Ok(RawModeGuard {
+            tty,
+            restored: false,
+        })
+    }
+
+    /// The TTY that was put into raw mode.
+    pub fn tty(&self) -> TtyHandle {
+        self.tty
+    }
+
+    /// Reset the terminal now, rather than when the guard is dropped, and return any error.
+    pub fn restore(mut self) -> crate::Result<()> {
+        self.restored = true;
+        TtyHandle::reset_mode()
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = TtyHandle::reset_mode();
+        }
+    }
+}
+
+impl TtyHandle {
+    /// Put the TTY into raw mode until the returned guard is dropped. See RawModeGuard.
+    pub fn raw_mode(&self) -> crate::Result<RawModeGuard> {
+        RawModeGuard::new(self, TtyMode::Raw)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{HandleTrait, SignalHandle, TimerHandle, TtyHandle};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+callbacks! {
+    pub ResizeCB(tty: TtyHandle, cols: i32, rows: i32);
+}
+
+/// Terminals send several SIGWINCH while a window is being dragged. The size is only checked
+/// once no signal has arrived for this long, in milliseconds.
+const RESIZE_DEBOUNCE: u64 = 50;
+
+/// The state of an on_resize() watcher.
+struct Resize {
+    cb: ResizeCB<'static>,
+    size: (i32, i32),
+}
+
+type State = Rc<RefCell<Resize>>;
+
+/// The SIGWINCH handler and debounce timer of a TTY. It is stored on the TTY, so both handles are
+/// closed when the TTY is closed.
+pub(crate) struct ResizeWatcher {
+    signal: SignalHandle,
+    timer: TimerHandle,
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        if !self.signal.is_closing() {
+            self.signal.close(());
+        }
+        if !self.timer.is_closing() {
+            self.timer.close(());
+        }
+    }
+}
+
+/// Called once the signals have settled down: report the new size, if it changed.
+fn check_size(tty: TtyHandle, state: &State) {
+    let (cols, rows) = match tty.get_winsize() {
+        Ok(size) => size,
+        Err(_) => return,
+    };
+
+    let mut cb = {
+        let mut s = state.borrow_mut();
+        if s.size == (cols, rows) {
+            return;
+        }
+        s.size = (cols, rows);
+        std::mem::take(&mut s.cb)
+    };
+    cb.call(tty, cols, rows);
+
+    let mut s = state.borrow_mut();
+    s.cb.restore(cb);
+}
+
+impl TtyHandle {
+    /// Call the callback with the new size of the terminal, in columns and rows, whenever it
+    /// changes. SIGWINCH is debounced, so a window that is being dragged does not produce a flood
+    /// of events.
+    ///
+    /// Calling on_resize() again replaces the callback. Passing () stops watching. The watcher is
+    /// also stopped when the TTY is closed, and does not keep the loop alive by itself.
+    pub fn on_resize<CB: Into<ResizeCB<'static>>>(&mut self, cb: CB) -> crate::Result<()> {
+        self.set_resize_watcher(None);
+        let cb = cb.into();
+        if cb.is_nil() {
+            return Ok(());
+        }
+
+        let state = Rc::new(RefCell::new(Resize {
+            cb,
+            size: self.get_winsize()?,
+        }));
+
+        let r#loop = self.get_loop();
+        let mut timer = r#loop.timer()?;
+        let mut signal = match r#loop.signal() {
+            Ok(signal) => signal,
+            Err(e) => {
+                timer.close(());
+                return Err(e);
+            }
+        };
+        timer.unref();
+        signal.unref();
+
+        // the watcher is created now so that both handles are closed on error
+        let watcher = ResizeWatcher { signal, timer };
+        let tty = *self;
+        signal.start(
+            move |_, _| {
+                let state = state.clone();
+                let mut timer = timer;
+                let _ = timer.start(RESIZE_DEBOUNCE, 0, move |_| check_size(tty, &state));
+            },
+            crate::Signal::SIGWINCH,
+        )?;
+        self.set_resize_watcher(Some(watcher));
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{TtyHandle, VTermState};
+
+/// A foreground or background color.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Color {
+    /// The terminal's default color.
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+
+    /// A color from the 256-color palette. 0-7 are the colors above, and 8-15 are their bright
+    /// versions.
+    Indexed(u8),
+
+    /// A 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+bitflags! {
+    /// Text attributes.
+    pub struct Attributes: u8 {
+        const BOLD = 1;
+        const DIM = 2;
+        const ITALIC = 4;
+        const UNDERLINE = 8;
+        const REVERSE = 16;
+    }
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes::empty()
+    }
+}
+
+/// The style of a span of text. The default style leaves the terminal's colors alone.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+}
+
+impl Style {
+    /// Create a new, plain, style.
+    pub fn new() -> Style {
+        Default::default()
+    }
+
+    /// Set the foreground color.
+    pub fn fg(mut self, color: Color) -> Style {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Set the background color.
+    pub fn bg(mut self, color: Color) -> Style {
+        self.bg = Some(color);
+        self
+    }
+
+    /// Make the text bold.
+    pub fn bold(mut self) -> Style {
+        self.attributes |= Attributes::BOLD;
+        self
+    }
+
+    /// Make the text dim.
+    pub fn dim(mut self) -> Style {
+        self.attributes |= Attributes::DIM;
+        self
+    }
+
+    /// Make the text italic.
+    pub fn italic(mut self) -> Style {
+        self.attributes |= Attributes::ITALIC;
+        self
+    }
+
+    /// Underline the text.
+    pub fn underline(mut self) -> Style {
+        self.attributes |= Attributes::UNDERLINE;
+        self
+    }
+
+    /// Swap the foreground and background colors.
+    pub fn reverse(mut self) -> Style {
+        self.attributes |= Attributes::REVERSE;
+        self
+    }
+
+    /// Returns true if the style does not change anything.
+    pub fn is_plain(&self) -> bool {
+        self.fg.is_none() && self.bg.is_none() && self.attributes.is_empty()
+    }
+}
+
+/// Maps a color to one of the 8 basic colors, for consoles that only emulate those.
+fn basic_color(color: Color) -> Color {
+    let (r, g, b) = match color {
+        Color::Indexed(n) if n < 8 => return basic_color_index(n),
+        Color::Indexed(n) if n < 16 => return basic_color_index(n - 8),
+        Color::Indexed(n) if n < 232 => {
+            // the 6x6x6 color cube
+            let n = n - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        Color::Indexed(n) => {
+            // the grayscale ramp
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+        Color::Rgb(r, g, b) => (r, g, b),
+        color => return color,
+    };
+    let bit = |v: u8, shift: u8| ((v >= 128) as u8) << shift;
+    basic_color_index(bit(r, 0) | bit(g, 1) | bit(b, 2))
+}
+
+fn basic_color_index(n: u8) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+/// Appends the SGR parameters for a color. base is 30 for the foreground or 40 for the
+/// background.
+fn push_color(codes: &mut Vec<String>, color: Color, base: u8) {
+    match color {
+        Color::Default => codes.push((base + 9).to_string()),
+        Color::Black => codes.push(base.to_string()),
+        Color::Red => codes.push((base + 1).to_string()),
+        Color::Green => codes.push((base + 2).to_string()),
+        Color::Yellow => codes.push((base + 3).to_string()),
+        Color::Blue => codes.push((base + 4).to_string()),
+        Color::Magenta => codes.push((base + 5).to_string()),
+        Color::Cyan => codes.push((base + 6).to_string()),
+        Color::White => codes.push((base + 7).to_string()),
+        Color::Indexed(n) => codes.push(format!("{};5;{}", base + 8, n)),
+        Color::Rgb(r, g, b) => codes.push(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+/// Returns true if styled output should be written to fd: it must be a TTY, NO_COLOR must not be
+/// set (see https://no-color.org), and TERM must not be "dumb".
+pub fn supports_styling(fd: crate::File) -> bool {
+    if crate::guess_handle(fd) != crate::HandleType::TTY {
+        return false;
+    }
+    match std::env::var_os("NO_COLOR") {
+        Some(value) if !value.is_empty() => return false,
+        _ => {}
+    }
+    std::env::var("TERM").ok().as_deref() != Some("dumb")
+}
+
+/// Writes styled text to a TTY as ANSI escape sequences.
+///
+/// Output is collected into a frame, and flush() sends the whole frame with a single write, so
+/// that a screen that is being redrawn does not flicker.
+///
+/// Whether to emit escape sequences at all is decided when the writer is created, using
+/// supports_styling(). When styling is off, text is written without colors or attributes, and
+/// cursor movement and clearing are left out entirely. The writer also tracks
+/// TtyHandle::get_vterm_state(): if the console does not process escape sequences itself (on
+/// Windows), libuv emulates them, but only supports the 8 basic colors, so other colors are mapped
+/// to the nearest basic color.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{Color, Style, StyledWriter};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let tty = r#loop.tty(1).unwrap();
+/// let mut writer = StyledWriter::new(&tty, 1);
+/// writer
+///     .clear_screen()
+///     .styled("error: ", Style::new().fg(Color::Red).bold())
+///     .text("something went wrong\n");
+/// writer.flush().unwrap();
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+pub struct StyledWriter {
+    tty: TtyHandle,
+    styling: bool,
+    vterm_state: Option<VTermState>,
+    frame: String,
+}
+
+impl StyledWriter {
+    /// Create a writer for the TTY. fd is the file descriptor that the TTY was opened with, and is
+    /// used to detect whether styling should be enabled.
+    pub fn new(tty: &TtyHandle, fd: crate::File) -> StyledWriter {
+        StyledWriter {
+            tty: *tty,
+            styling: supports_styling(fd),
+            vterm_state: TtyHandle::get_vterm_state().ok(),
+            frame: String::new(),
+        }
+    }
+
+    /// Override whether escape sequences are emitted.
+    pub fn styling(mut self, styling: bool) -> StyledWriter {
+        self.styling = styling;
+        self
+    }
+
+    /// Returns true if escape sequences are emitted.
+    pub fn is_styling(&self) -> bool {
+        self.styling
+    }
+
+    /// The virtual terminal state of the console, as reported by TtyHandle::get_vterm_state(), or
+    /// None where that is not supported (ie, on Unix, where the terminal always processes escape
+    /// sequences itself).
+    pub fn vterm_state(&self) -> Option<VTermState> {
+        self.vterm_state
+    }
+
+    /// Read the virtual terminal state again, such as after a call to
+    /// TtyHandle::set_vterm_state().
+    pub fn refresh_vterm_state(&mut self) {
+        self.vterm_state = TtyHandle::get_vterm_state().ok();
+    }
+
+    /// The TTY that is being written to.
+    pub fn tty(&self) -> TtyHandle {
+        self.tty
+    }
+
+    /// The number of bytes in the current frame.
+    pub fn pending(&self) -> usize {
+        self.frame.len()
+    }
+
+    /// Add plain text to the frame.
+    pub fn text(&mut self, text: &str) -> &mut StyledWriter {
+        self.frame.push_str(text);
+        self
+    }
+
+    /// Add text with the given style to the frame. The style is reset after the text.
+    pub fn styled(&mut self, text: &str, style: Style) -> &mut StyledWriter {
+        if !self.styling || style.is_plain() {
+            return self.text(text);
+        }
+
+        let basic = self.vterm_state == Some(VTermState::Unsupported);
+        let mut codes = Vec::new();
+        for (attribute, code) in [
+            (Attributes::BOLD, "1"),
+            (Attributes::DIM, "2"),
+            (Attributes::ITALIC, "3"),
+            (Attributes::UNDERLINE, "4"),
+            (Attributes::REVERSE, "7"),
+        ]
+        .iter()
+        {
+            if style.attributes.contains(*attribute) {
+                codes.push(code.to_string());
+            }
+        }
+        for (color, base) in [(style.fg, 30), (style.bg, 40)].iter() {
+            if let Some(color) = color {
+                let color = if basic { basic_color(*color) } else { *color };
+                push_color(&mut codes, color, *base);
+            }
+        }
+
+        self.frame
+            .push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text));
+        self
+    }
+
+    /// Add an escape sequence to the frame, if styling is on.
+    fn control(&mut self, sequence: &str) -> &mut StyledWriter {
+        if self.styling {
+            self.frame.push_str(sequence);
+        }
+        self
+    }
+
+    /// Move the cursor to the given column and row, which are 0-based.
+    pub fn move_to(&mut self, column: u16, row: u16) -> &mut StyledWriter {
+        self.control(&format!("\x1b[{};{}H", row as u32 + 1, column as u32 + 1))
+    }
+
+    /// Move the cursor up by n rows.
+    pub fn move_up(&mut self, n: u16) -> &mut StyledWriter {
+        if n == 0 {
+            return self;
+        }
+        self.control(&format!("\x1b[{}A", n))
+    }
+
+    /// Move the cursor down by n rows.
+    pub fn move_down(&mut self, n: u16) -> &mut StyledWriter {
+        if n == 0 {
+            return self;
+        }
+        self.control(&format!("\x1b[{}B", n))
+    }
+
+    /// Move the cursor right by n columns.
+    pub fn move_right(&mut self, n: u16) -> &mut StyledWriter {
+        if n == 0 {
+            return self;
+        }
+        self.control(&format!("\x1b[{}C", n))
+    }
+
+    /// Move the cursor left by n columns.
+    pub fn move_left(&mut self, n: u16) -> &mut StyledWriter {
+        if n == 0 {
+            return self;
+        }
+        self.control(&format!("\x1b[{}D", n))
+    }
+
+    /// Clear the screen and move the cursor to the top left corner.
+    pub fn clear_screen(&mut self) -> &mut StyledWriter {
+        self.control("\x1b[2J\x1b[H")
+    }
+
+    /// Clear the line that the cursor is on, and move the cursor to the start of it.
+    pub fn clear_line(&mut self) -> &mut StyledWriter {
+        self.control("\x1b[2K\r")
+    }
+
+    /// Hide the cursor.
+    pub fn hide_cursor(&mut self) -> &mut StyledWriter {
+        self.control("\x1b[?25l")
+    }
+
+    /// Show the cursor.
+    pub fn show_cursor(&mut self) -> &mut StyledWriter {
+        self.control("\x1b[?25h")
+    }
+
+    /// Throw away the current frame without writing it.
+    pub fn discard(&mut self) {
+        self.frame.clear();
+    }
+
+    /// Write the current frame to the TTY with a single write, and start a new frame.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+
+        let frame = std::mem::take(&mut self.frame);
+        crate::write_copy(&mut self.tty, frame.as_bytes(), |_| {})
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{HandleTrait, TimerHandle};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// A timer and its schedule(), as found by VirtualClock::next_timer().
+type NextTimer = (TimerHandle, (u64, u64));
+
+/// A controllable clock for testing timer-driven code without waiting on real time. The clock
+/// starts at the loop's current now() and only moves when advance() is called. Timers are fired
+/// straight from the loop's timer heap, in the same order libuv would fire them, and the loop's
+/// now() reports the virtual time while their callbacks run - so timers started from inside a
+/// callback are scheduled relative to the virtual clock, too.
+///
+/// Note: run() updates the loop's concept of "now" from the real clock. Timers will not fire on
+/// virtual time if the loop is run, and may fire on real time if they were started before the
+/// VirtualClock. Use run(RunMode::NoWait) if other events need to be processed between calls to
+/// advance().
+///
+/// Note: A timer that keeps restarting itself with a timeout of zero will cause advance() to loop
+/// forever, since it is always due.
+pub struct VirtualClock {
+    r#loop: crate::Loop,
+    now: u64,
+}
+
+impl VirtualClock {
+    /// Create a new VirtualClock for the given loop, starting at the loop's now().
+    pub fn new(r#loop: &crate::Loop) -> VirtualClock {
+        VirtualClock {
+            r#loop: r#loop.clone(),
+            now: r#loop.now(),
+        }
+    }
+
+    /// The current virtual time, in milliseconds.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Advance the clock by the given number of milliseconds, firing every timer that comes due in
+    /// order. Returns the number of timer callbacks that were called.
+    pub fn advance(&mut self, ms: u64) -> usize {
+        let target = self.now.saturating_add(ms);
+        let mut fired = 0;
+        while let Some((mut timer, due)) = self.next_timer().filter(|(_, due)| *due <= target) {
+            // timers that are overdue run at the current time, just like a real loop
+            self.now = self.now.max(due);
+            self.r#loop.set_time(self.now);
+            timer.expire();
+            fired += 1;
+        }
+
+        self.now = target;
+        self.r#loop.set_time(self.now);
+        fired
+    }
+
+    /// Advance the clock to the next deadline, if there is one, firing all of the timers that are
+    /// due at that time. Returns the number of timer callbacks that were called.
+    pub fn advance_to_next(&mut self) -> usize {
+        match self.next_deadline() {
+            Some(ms) => self.advance(ms),
+            None => 0,
+        }
+    }
+
+    /// The number of milliseconds until the next timer is due, or None if there are no active
+    /// timers. Returns zero if a timer is overdue.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.next_timer()
+            .map(|(_, due)| due.saturating_sub(self.now))
+    }
+
+    /// Find the active timer that libuv would run next, along with when it is due.
+    fn next_timer(&self) -> Option<(TimerHandle, u64)> {
+        let next: Rc<RefCell<Option<NextTimer>>> = Rc::new(RefCell::new(None));
+        let walk_next = next.clone();
+        self.r#loop.walk(move |handle| {
+            if let Ok(timer) = TimerHandle::try_from(handle) {
+                if !timer.is_active() || timer.is_closing() {
+                    return;
+                }
+
+                let schedule = timer.schedule();
+                let mut next = walk_next.borrow_mut();
+                match *next {
+                    Some((_, s)) if s <= schedule => {}
+                    _ => *next = Some((timer, schedule)),
+                }
+            }
+        });
+        let next = next.borrow_mut().take();
+        next.map(|(timer, (due, _))| (timer, due))
+    }
+}
@@ -0,0 +1,286 @@
+use crate::{File, FromInner, FsReq, FsType, HandleTrait, Inner, IntoInner};
+use std::collections::HashMap;
+use uv::{uv_buf_t, uv_fs_t};
+
+/// A fault to inject into file system operations. See Loop::inject_fs_fault().
+#[derive(Clone, Copy, Debug)]
+pub enum FsFault {
+    /// The operation is not run. Instead, it fails with the given error. Async operations receive
+    /// the error through req.result() in their callback; sync operations return it.
+    Fail(crate::Error),
+
+    /// Read and write operations transfer at most this many bytes. Other operations are
+    /// unaffected.
+    ShortCount(usize),
+
+    /// The callback for async operations is delayed by this many milliseconds after the operation
+    /// completes. Sync operations sleep for this long before running.
+    Delay(u64),
+}
+
+/// Describes which file system operations a fault applies to.
+#[derive(Clone, Debug)]
+pub struct FsFaultRule {
+    fs_type: FsType,
+    pattern: String,
+    fault: FsFault,
+    remaining: Option<usize>,
+}
+
+impl FsFaultRule {
+    /// Create a new rule that applies the fault to operations of the given type on paths that
+    /// match pattern. In the pattern, `*` matches any number of characters and `?` matches exactly
+    /// one.
+    ///
+    /// Operations on a File, such as fs_read() or fs_write(), are matched against the path that
+    /// the File was opened with, if it was opened with fs_open() on this loop. Otherwise, they are
+    /// matched against an empty path.
+    pub fn new(fs_type: FsType, pattern: &str, fault: FsFault) -> FsFaultRule {
+        FsFaultRule {
+            fs_type,
+            pattern: pattern.to_owned(),
+            fault,
+            remaining: None,
+        }
+    }
+
+    /// Only apply the fault to the next n matching operations. By default, rules apply forever.
+    pub fn times(mut self, n: usize) -> FsFaultRule {
+        self.remaining = Some(n);
+        self
+    }
+
+    /// Returns true if the rule applies to the given operation.
+    fn matches(&self, fs_type: FsType, path: &str) -> bool {
+        self.fs_type == fs_type && self.remaining != Some(0) && glob_match(&self.pattern, path)
+    }
+}
+
+/// The target of a file system operation.
+pub(crate) enum FsTarget<'a> {
+    Path(&'a std::ffi::CStr),
+    File(File),
+    None,
+}
+
+/// The fault rules registered on a loop, along with the paths of files opened on the loop.
+#[derive(Default)]
+pub(crate) struct FsFaults {
+    rules: Vec<FsFaultRule>,
+    files: HashMap<File, String>,
+}
+
+impl FsFaults {
+    /// Find the faults that apply to an operation. Rules with a limited number of uses are used up.
+    fn take(
+        &mut self,
+        fs_type: FsType,
+        target: &FsTarget,
+        want: fn(&FsFault) -> bool,
+    ) -> Vec<FsFault> {
+        let path = match target {
+            FsTarget::Path(path) => path.to_string_lossy().into_owned(),
+            FsTarget::File(file) => self.files.get(file).cloned().unwrap_or_default(),
+            FsTarget::None => String::new(),
+        };
+
+        let mut faults = Vec::new();
+        for rule in self.rules.iter_mut() {
+            if want(&rule.fault) && rule.matches(fs_type, &path) {
+                if let Some(remaining) = rule.remaining.as_mut() {
+                    *remaining -= 1;
+                }
+                faults.push(rule.fault);
+            }
+        }
+        self.rules.retain(|rule| rule.remaining != Some(0));
+        faults
+    }
+}
+
+/// Returns true if path matches the glob pattern. `*` matches any number of characters and `?`
+/// matches exactly one.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < path.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == path[s]) {
+            p += 1;
+            s += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, s));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last * match one more character and try again
+            p = star + 1;
+            s = matched + 1;
+            backtrack = Some((star, s));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Finish a request that was delayed or failed by a fault: libuv's completion callback is called
+/// after ms milliseconds.
+fn complete_later(r#loop: &crate::Loop, req: *mut uv_fs_t, ms: u64) {
+    let result = r#loop.timer().and_then(|mut timer| {
+        timer.start(ms, 0, move |mut timer: crate::TimerHandle| {
+            crate::uv_fs_cb(req);
+            timer.close(());
+        })
+    });
+    if result.is_err() {
+        // no timer - complete the request right away
+        crate::uv_fs_cb(req);
+    }
+}
+
+/// Called from uv_fs_cb before the request's callback runs. Remembers the paths of opened files
+/// and applies Delay faults. Returns true if the callback has been delayed.
+pub(crate) fn on_fs_complete(req: *mut uv_fs_t) -> bool {
+    let mut fsreq = FsReq::from_inner(req);
+    let r#loop = fsreq.r#loop();
+    record_open(&r#loop, &fsreq);
+
+    let delay = fsreq.take_fault_delay();
+    if delay > 0 {
+        complete_later(&r#loop, req, delay);
+        return true;
+    }
+    false
+}
+
+/// Remember the path that a file was opened with, so that operations on the File can be matched
+/// against fault rules.
+pub(crate) fn record_open(r#loop: &crate::Loop, req: &FsReq) {
+    if req.request_type() != FsType::OPEN {
+        return;
+    }
+
+    if let (Ok(file), Some(faults)) = (req.result(), r#loop.fs_faults()) {
+        faults.borrow_mut().files.insert(file as _, req.path());
+    }
+}
+
+impl crate::Loop {
+    /// Inject a fault into file system operations run on this loop. Rules are checked in the order
+    /// they were added, and all matching rules apply: if more than one Delay matches, for example,
+    /// the delays are added together.
+    pub fn inject_fs_fault(&self, rule: FsFaultRule) {
+        if let Some(faults) = self.fs_faults() {
+            faults.borrow_mut().rules.push(rule);
+        }
+    }
+
+    /// Remove all fault rules from this loop.
+    pub fn clear_fs_faults(&self) {
+        if let Some(faults) = self.fs_faults() {
+            faults.borrow_mut().rules.clear();
+        }
+    }
+
+    /// Called by the fs_* functions before an operation is started. Applies Fail and Delay faults.
+    ///
+    /// If a sync operation fails, the request is destroyed and the error is returned. If an async
+    /// operation fails, the callback is scheduled and Ok(true) is returned - the operation must not
+    /// be started.
+    pub(crate) fn fs_fault_start(
+        &self,
+        req: &mut FsReq,
+        fs_type: FsType,
+        target: FsTarget,
+        is_async: bool,
+    ) -> crate::Result<bool> {
+        let faults = match self.fs_faults() {
+            Some(faults) => faults.borrow_mut().take(fs_type, &target, |fault| {
+                matches!(fault, FsFault::Fail(_) | FsFault::Delay(_))
+            }),
+            None => return Ok(false),
+        };
+
+        let mut delay = 0;
+        let mut error = None;
+        for fault in faults {
+            match fault {
+                FsFault::Fail(e) if error.is_none() => error = Some(e),
+                FsFault::Delay(ms) => delay += ms,
+                _ => {}
+            }
+        }
+
+        if !is_async {
+            if delay > 0 {
+                crate::sleep(delay as _);
+            }
+            return match error {
+                Some(e) => {
+                    req.destroy();
+                    Err(e)
+                }
+                None => Ok(false),
+            };
+        }
+
+        match error {
+            Some(e) => {
+                let ptr: *mut uv_fs_t = req.inner();
+                unsafe {
+                    // libuv never saw this request, so fill in the fields that a completed request
+                    // would have. The data pointer is the only thing we need to keep.
+                    let data = (*ptr).data;
+                    std::ptr::write_bytes(ptr, 0, 1);
+                    (*ptr).data = data;
+                    (*ptr).type_ = uv::uv_req_type_UV_FS;
+                    (*ptr).loop_ = self.into_inner();
+                    (*ptr).fs_type = (&fs_type).into_inner();
+                    (*ptr).result = IntoInner::<uv::uv_errno_t>::into_inner(e) as _;
+                }
+                complete_later(self, ptr, delay);
+                Ok(true)
+            }
+            None => {
+                req.set_fault_delay(delay);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Called by fs_read() and fs_write() to apply ShortCount faults. The buffers are shortened so
+    /// that they hold, at most, the smallest matching count.
+    pub(crate) fn fs_fault_short_count(
+        &self,
+        fs_type: FsType,
+        file: File,
+        bufs_ptr: *mut uv_buf_t,
+        bufs_len: usize,
+    ) {
+        let faults = match self.fs_faults() {
+            Some(faults) => faults
+                .borrow_mut()
+                .take(fs_type, &FsTarget::File(file), |fault| {
+                    matches!(fault, FsFault::ShortCount(_))
+                }),
+            None => return,
+        };
+
+        let limit = faults
+            .iter()
+            .filter_map(|fault| match fault {
+                FsFault::ShortCount(count) => Some(*count),
+                _ => None,
+            })
+            .min();
+        if let Some(mut limit) = limit {
+            let bufs = unsafe { std::slice::from_raw_parts_mut(bufs_ptr, bufs_len) };
+            for buf in bufs {
+                let len = std::cmp::min(buf.len as _, limit);
+                buf.len = len as _;
+                limit -= len;
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod clock;
+pub use clock::*;
+
+pub mod fs_faults;
+pub use fs_faults::*;
@@ -0,0 +1,258 @@
+use crate::{FsEvent, FsEventHandle, FsPollHandle, Signal, SignalHandle, Stat, StreamHandle};
+use std::collections::BTreeMap;
+
+/// Events that are coalesced by a Batcher. A batch is created from the first event of a burst,
+/// and every following event is added to it until the batch is delivered.
+pub trait Batch: 'static {
+    /// The event that is added to the batch.
+    type Event;
+
+    /// Create a batch that holds a single event.
+    fn new(event: Self::Event) -> Self;
+
+    /// Add an event to the batch.
+    fn add(&mut self, event: Self::Event);
+}
+
+/// The simplest batch: every event, in the order that they arrived.
+impl<T: 'static> Batch for Vec<T> {
+    type Event = T;
+
+    fn new(event: T) -> Vec<T> {
+        vec![event]
+    }
+
+    fn add(&mut self, event: T) {
+        self.push(event);
+    }
+}
+
+/// The FsEventHandle callbacks of a burst. The events that were reported for each file are merged.
+pub struct FsEventBatch {
+    handle: FsEventHandle,
+    events: BTreeMap<Option<String>, FsEvent>,
+    errors: Vec<crate::Error>,
+    count: usize,
+}
+
+impl FsEventBatch {
+    /// The handle that reported the events.
+    pub fn handle(&self) -> FsEventHandle {
+        self.handle
+    }
+
+    /// The merged events for each file name. The file name is None if the platform did not
+    /// report one.
+    pub fn events(&self) -> &BTreeMap<Option<String>, FsEvent> {
+        &self.events
+    }
+
+    /// Errors that the handle reported during the burst.
+    pub fn errors(&self) -> &[crate::Error] {
+        &self.errors
+    }
+
+    /// The number of callbacks that were coalesced into this batch.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Batch for FsEventBatch {
+    type Event = (FsEventHandle, Option<String>, FsEvent, crate::Result<u32>);
+
+    fn new(event: Self::Event) -> FsEventBatch {
+        let mut batch = FsEventBatch {
+            handle: event.0,
+            events: BTreeMap::new(),
+            errors: Vec::new(),
+            count: 0,
+        };
+        batch.add(event);
+        batch
+    }
+
+    fn add(&mut self, (handle, filename, events, status): Self::Event) {
+        self.handle = handle;
+        self.count += 1;
+        match status {
+            Ok(_) => *self.events.entry(filename).or_insert_with(FsEvent::empty) |= events,
+            Err(e) => self.errors.push(e),
+        }
+    }
+}
+
+/// The FsPollHandle callbacks of a burst, summarized as a single change: from the previous stat of
+/// the first callback to the current stat of the last one.
+pub struct FsPollBatch {
+    handle: FsPollHandle,
+    status: crate::Result<u32>,
+    prev: Stat,
+    curr: Stat,
+    count: usize,
+}
+
+impl FsPollBatch {
+    /// The handle that reported the changes.
+    pub fn handle(&self) -> FsPollHandle {
+        self.handle
+    }
+
+    /// The status of the last callback.
+    pub fn status(&self) -> crate::Result<u32> {
+        self.status
+    }
+
+    /// The stat before the first change.
+    pub fn prev(&self) -> &Stat {
+        &self.prev
+    }
+
+    /// The stat after the last change.
+    pub fn curr(&self) -> &Stat {
+        &self.curr
+    }
+
+    /// The number of callbacks that were coalesced into this batch.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Batch for FsPollBatch {
+    type Event = (FsPollHandle, crate::Result<u32>, Stat, Stat);
+
+    fn new((handle, status, prev, curr): Self::Event) -> FsPollBatch {
+        FsPollBatch {
+            handle,
+            status,
+            prev,
+            curr,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, (handle, status, _, curr): Self::Event) {
+        self.handle = handle;
+        self.status = status;
+        self.curr = curr;
+        self.count += 1;
+    }
+}
+
+/// The ReadCB callbacks of a burst: the data that was read, concatenated, and the error that ended
+/// the reads, if any.
+pub struct ReadBatch {
+    stream: StreamHandle,
+    data: Vec<u8>,
+    error: Option<crate::Error>,
+    count: usize,
+}
+
+impl ReadBatch {
+    /// The stream that was read from.
+    pub fn stream(&self) -> StreamHandle {
+        self.stream
+    }
+
+    /// The data that was read.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The data that was read.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The first error that a read reported, such as EOF.
+    pub fn error(&self) -> Option<crate::Error> {
+        self.error
+    }
+
+    /// Returns true if the end of the stream was reached.
+    pub fn is_eof(&self) -> bool {
+        self.error == Some(crate::Error::EOF)
+    }
+
+    /// The number of callbacks that were coalesced into this batch.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Batch for ReadBatch {
+    type Event = (StreamHandle, crate::Result<Vec<u8>>);
+
+    fn new(event: Self::Event) -> ReadBatch {
+        let mut batch = ReadBatch {
+            stream: event.0,
+            data: Vec::new(),
+            error: None,
+            count: 0,
+        };
+        batch.add(event);
+        batch
+    }
+
+    fn add(&mut self, (stream, data): Self::Event) {
+        self.stream = stream;
+        self.count += 1;
+        match data {
+            Ok(data) => self.data.extend_from_slice(&data),
+            Err(e) => {
+                if self.error.is_none() {
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+/// The SignalHandle callbacks of a burst.
+pub struct SignalBatch {
+    handle: SignalHandle,
+    signals: Vec<(Signal, usize)>,
+}
+
+impl SignalBatch {
+    /// The handle that received the signals.
+    pub fn handle(&self) -> SignalHandle {
+        self.handle
+    }
+
+    /// Each signal that arrived, with the number of times that it arrived, in the order in which
+    /// the signals first arrived.
+    pub fn signals(&self) -> &[(Signal, usize)] {
+        &self.signals
+    }
+
+    /// Returns true if the signal arrived during the burst.
+    pub fn contains(&self, signal: Signal) -> bool {
+        self.signals.iter().any(|(s, _)| *s == signal)
+    }
+
+    /// The number of callbacks that were coalesced into this batch.
+    pub fn count(&self) -> usize {
+        self.signals.iter().map(|(_, n)| n).sum()
+    }
+}
+
+impl Batch for SignalBatch {
+    type Event = (SignalHandle, Signal);
+
+    fn new((handle, signal): Self::Event) -> SignalBatch {
+        SignalBatch {
+            handle,
+            signals: vec![(signal, 1)],
+        }
+    }
+
+    fn add(&mut self, (handle, signal): Self::Event) {
+        self.handle = handle;
+        match self.signals.iter_mut().find(|(s, _)| *s == signal) {
+            Some((_, n)) => *n += 1,
+            None => self.signals.push((signal, 1)),
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::str::FromStr;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead next_after() searches before it gives up on a schedule that never matches, such
+/// as "0 0 31 2 *". February 29th can be eight years apart.
+const SEARCH_DAYS: i64 = 9 * 366;
+
+/// The time zone that a Schedule's wall-clock times are in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScheduleZone {
+    /// The local time zone, including daylight saving time.
+    Local,
+
+    /// Coordinated Universal Time.
+    Utc,
+}
+
+/// When a scheduled job runs: a set of minutes, hours, days of the month, months, and days of the
+/// week, in the local time zone or UTC.
+///
+/// A schedule is parsed from a string, which can be:
+///
+/// * A cron expression with five fields: minute, hour, day of month, month, and day of week. Each
+///   field is `*`, a number, a range such as `1-5`, or a list such as `1,15`, and ranges may have a
+///   step, as in `*/15` or `0-30/10`. Months and days of the week can be given as names (`jan`,
+///   `mon`), and Sunday is either 0 or 7. If both the day of month and the day of week are
+///   restricted, a day matches if either one does.
+/// * One of `@yearly` (or `@annually`), `@monthly`, `@weekly`, `@daily` (or `@midnight`), and
+///   `@hourly`.
+/// * A phrase such as "every minute", "every 15 minutes", "every hour", "every 6 hours", "every
+///   day at 02:00", "every monday at 9:30", "every weekday at 08:00" or "every weekend at 10:00".
+///   A phrase may end with "local" or "utc".
+///
+/// Schedules are in local time unless they are made into UTC schedules with utc(). A local time
+/// that is skipped when daylight saving time starts runs after the change instead, shifted by the
+/// length of the gap; a local time that repeats when daylight saving time ends only runs the first
+/// time.
+///
+/// ```
+/// use libuv::Schedule;
+///
+/// let nightly: Schedule = "every day at 02:00 local".parse().unwrap();
+/// let quarterly: Schedule = "0 0 1 */3 *".parse().unwrap();
+/// assert_eq!(nightly, "0 2 * * *".parse().unwrap());
+/// assert!(quarterly.utc().next_after(0).is_some());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+    zone: ScheduleZone,
+}
+
+impl Schedule {
+    /// Parse a cron expression or one of the @ shortcuts. See the type's documentation for the
+    /// syntax.
+    pub fn cron(expr: &str) -> crate::Result<Schedule> {
+        let expr = match expr.trim().to_ascii_lowercase().as_str() {
+            "@yearly" | "@annually" => "0 0 1 1 *".to_owned(),
+            "@monthly" => "0 0 1 * *".to_owned(),
+            "@weekly" => "0 0 * * 0".to_owned(),
+            "@daily" | "@midnight" => "0 0 * * *".to_owned(),
+            "@hourly" => "0 * * * *".to_owned(),
+            expr => expr.to_owned(),
+        };
+
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(crate::Error::EINVAL);
+        }
+
+        // Sunday is both 0 and 7
+        let weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS)?;
+        let weekdays = (weekdays | weekdays >> 7) & 0x7f;
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59, &[])?,
+            hours: parse_field(fields[1], 0, 23, &[])? as _,
+            days: parse_field(fields[2], 1, 31, &[])? as _,
+            months: parse_field(fields[3], 1, 12, &MONTHS)? as _,
+            weekdays: weekdays as _,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+            zone: ScheduleZone::Local,
+        })
+    }
+
+    /// Run every day at the given time.
+    pub fn daily(hour: u32, minute: u32) -> crate::Result<Schedule> {
+        if hour > 23 || minute > 59 {
+            return Err(crate::Error::EINVAL);
+        }
+        Schedule::cron(&format!("{} {} * * *", minute, hour))
+    }
+
+    /// Use UTC for the schedule's times.
+    pub fn utc(mut self) -> Schedule {
+        self.zone = ScheduleZone::Utc;
+        self
+    }
+
+    /// Use the local time zone for the schedule's times. This is the default.
+    pub fn local(mut self) -> Schedule {
+        self.zone = ScheduleZone::Local;
+        self
+    }
+
+    /// The time zone of the schedule's times.
+    pub fn zone(&self) -> ScheduleZone {
+        self.zone
+    }
+
+    /// The first time that the schedule runs after the given time. Both times are in milliseconds
+    /// since the UNIX epoch, as returned by clock_gettime(ClockId::Realtime). Returns None if the
+    /// schedule never runs, such as on February 30th.
+    pub fn next_after(&self, time: i64) -> Option<i64> {
+        self.next_after_with(time, &|time| self.utc_offset(time))
+    }
+
+    /// next_after(), with the offset of the schedule's time zone from UTC, in seconds, given by
+    /// utc_offset.
+    fn next_after_with(&self, time: i64, utc_offset: &dyn Fn(i64) -> i64) -> Option<i64> {
+        // the first whole minute after the time
+        let start = time.div_euclid(60_000) * 60 + 60;
+        let mut local = start + utc_offset(start);
+        loop {
+            let candidate = self.next_local(local)?;
+            let utc = utc_from_local(candidate, utc_offset);
+            if utc >= start {
+                return Some(utc * 1000);
+            }
+
+            // the local time was repeated when daylight saving time ended, and its first occurrence
+            // is in the past
+            local = candidate + 60;
+        }
+    }
+
+    /// The first local time, in seconds, at or after the given one that matches.
+    fn next_local(&self, from: i64) -> Option<i64> {
+        let first = from.div_euclid(86_400);
+        let mut minute = from.rem_euclid(86_400) / 60;
+        for day in first..first + SEARCH_DAYS {
+            if self.matches_day(day) {
+                while minute < 1440 {
+                    let (hour, min) = (minute / 60, minute % 60);
+                    if self.hours & (1 << hour) == 0 {
+                        minute = (hour + 1) * 60;
+                    } else if self.minutes & (1 << min) == 0 {
+                        minute += 1;
+                    } else {
+                        return Some(day * 86_400 + minute * 60);
+                    }
+                }
+            }
+            minute = 0;
+        }
+        None
+    }
+
+    fn matches_day(&self, day: i64) -> bool {
+        let (_, month, mday) = civil_from_days(day);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+
+        let weekday = (day + 4).rem_euclid(7);
+        let day_matches = self.days & (1 << mday) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day_matches || weekday_matches,
+            _ => day_matches && weekday_matches,
+        }
+    }
+
+    /// The offset of the schedule's time zone from UTC at the given time, in seconds.
+    fn utc_offset(&self, time: i64) -> i64 {
+        match self.zone {
+            ScheduleZone::Utc => 0,
+            ScheduleZone::Local => local_utc_offset(time),
+        }
+    }
+
+    /// Parse a phrase such as "every day at 02:00 local".
+    fn phrase(phrase: &str) -> crate::Result<Schedule> {
+        let phrase = phrase.to_ascii_lowercase();
+        let mut words: Vec<&str> = phrase.split_whitespace().collect();
+        let zone = match words.last() {
+            Some(&"utc") => Some(ScheduleZone::Utc),
+            Some(&"local") => Some(ScheduleZone::Local),
+            _ => None,
+        };
+        if zone.is_some() {
+            words.pop();
+        }
+
+        let expr = match words.as_slice() {
+            ["every", "minute"] => "* * * * *".to_owned(),
+            ["every", "hour"] => "0 * * * *".to_owned(),
+            ["every", n, "minutes"] => format!("*/{} * * * *", step(n, 59)?),
+            ["every", n, "hours"] => format!("0 */{} * * *", step(n, 23)?),
+            ["every", days] => format!("0 0 {}", days_field(days)?),
+            ["every", days, "at", time] => {
+                let (hour, minute) = time_of_day(time)?;
+                format!("{} {} {}", minute, hour, days_field(days)?)
+            }
+            _ => return Err(crate::Error::EINVAL),
+        };
+
+        let schedule = Schedule::cron(&expr)?;
+        Ok(match zone {
+            Some(ScheduleZone::Utc) => schedule.utc(),
+            _ => schedule,
+        })
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = crate::Error;
+
+    /// Parses a cron expression, an @ shortcut, or a phrase. See the type's documentation.
+    fn from_str(s: &str) -> Result<Schedule, Self::Err> {
+        if s.trim_start().to_ascii_lowercase().starts_with("every") {
+            Schedule::phrase(s)
+        } else {
+            Schedule::cron(s)
+        }
+    }
+}
+
+/// Parse a cron field into a bitmask, in which bit n is set if value n matches.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> crate::Result<u64> {
+    let value = |s: &str| -> crate::Result<u32> {
+        let n = match names.iter().position(|name| *name == s) {
+            Some(i) => i as u32 + min,
+            None => s.parse().map_err(|_| crate::Error::EINVAL)?,
+        };
+        if n < min || n > max {
+            return Err(crate::Error::EINVAL);
+        }
+        Ok(n)
+    };
+
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.find('/') {
+            Some(i) => (&item[..i], Some(&item[i + 1..])),
+            None => (item, None),
+        };
+        let step = match step {
+            Some(step) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => step,
+                _ => return Err(crate::Error::EINVAL),
+            },
+            None => 1,
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            (value(&range[..i])?, value(&range[i + 1..])?)
+        } else {
+            let first = value(range)?;
+            // "5/10" means "5-max/10"
+            (first, if step > 1 { max } else { first })
+        };
+        if first > last {
+            return Err(crate::Error::EINVAL);
+        }
+        for n in (first..=last).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// The day of month, month, and day of week fields for a phrase's "every ..." days.
+fn days_field(days: &str) -> crate::Result<String> {
+    Ok(match days {
+        "day" => "* * *".to_owned(),
+        "weekday" => "* * 1-5".to_owned(),
+        "weekend" => "* * 0,6".to_owned(),
+        day => match WEEKDAYS
+            .iter()
+            .position(|name| day.starts_with(name) && day.ends_with("day"))
+        {
+            Some(weekday) => format!("* * {}", weekday),
+            None => return Err(crate::Error::EINVAL),
+        },
+    })
+}
+
+/// Parse the step of "every n minutes", which must be between 1 and max.
+fn step(n: &str, max: u32) -> crate::Result<u32> {
+    match n.parse() {
+        Ok(n) if n >= 1 && n <= max => Ok(n),
+        _ => Err(crate::Error::EINVAL),
+    }
+}
+
+/// Parse a time such as "02:00" into an hour and minute.
+fn time_of_day(time: &str) -> crate::Result<(u32, u32)> {
+    let i = time.find(':').ok_or(crate::Error::EINVAL)?;
+    let hour = time[..i].parse().map_err(|_| crate::Error::EINVAL)?;
+    let minute = time[i + 1..].parse().map_err(|_| crate::Error::EINVAL)?;
+    if hour > 23 || minute > 59 {
+        return Err(crate::Error::EINVAL);
+    }
+    Ok((hour, minute))
+}
+
+/// Convert a local time to UTC. A repeated local time becomes its first occurrence, and a skipped
+/// local time is converted with the offset from before the change.
+fn utc_from_local(local: i64, utc_offset: &dyn Fn(i64) -> i64) -> i64 {
+    // the offsets a day before and after are on either side of any daylight saving change
+    let before = utc_offset(local - 86_400);
+    let after = utc_offset(local + 86_400);
+    [before, after]
+        .iter()
+        .map(|offset| local - offset)
+        .filter(|utc| local - utc_offset(*utc) == *utc)
+        .min()
+        .unwrap_or(local - before)
+}
+
+/// The number of days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The year, month, and day of the given number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// The offset of the local time zone from UTC at the given time, in seconds. libuv does not deal
+/// with time zones, so this asks the C library. Zero if the time cannot be converted.
+fn local_utc_offset(time: i64) -> i64 {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let t = time as libc::time_t;
+
+    #[cfg(unix)]
+    let ok = !unsafe { libc::localtime_r(&t, &mut tm) }.is_null();
+
+    #[cfg(windows)]
+    let ok = unsafe { libc::localtime_s(&mut tm, &t) } == 0;
+
+    if !ok {
+        return 0;
+    }
+    let local = days_from_civil(
+        tm.tm_year as i64 + 1900,
+        tm.tm_mon as u32 + 1,
+        tm.tm_mday as u32,
+    ) * 86_400
+        + tm.tm_hour as i64 * 3600
+        + tm.tm_min as i64 * 60
+        + tm.tm_sec as i64;
+    local - time
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A UTC time, in milliseconds since the UNIX epoch.
+    fn utc(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> i64 {
+        (days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60) * 1000
+    }
+
+    /// US Eastern time in 2021: daylight saving time started on March 14th at 02:00 EST, and ended
+    /// on November 7th at 02:00 EDT.
+    fn eastern(time: i64) -> i64 {
+        let start = utc(2021, 3, 14, 7, 0) / 1000;
+        let end = utc(2021, 11, 7, 6, 0) / 1000;
+        if time >= start && time < end {
+            -4 * 3600
+        } else {
+            -5 * 3600
+        }
+    }
+
+    #[test]
+    fn dst_gap() {
+        // 02:30 is skipped on March 14th, so the job runs at 03:30 EDT instead
+        let schedule = Schedule::cron("30 2 * * *").unwrap();
+        let next = schedule.next_after_with(utc(2021, 3, 13, 12, 0), &eastern);
+        assert_eq!(next, Some(utc(2021, 3, 14, 7, 30)));
+
+        let next = schedule.next_after_with(next.unwrap(), &eastern);
+        assert_eq!(next, Some(utc(2021, 3, 15, 6, 30)));
+    }
+
+    #[test]
+    fn dst_repeat() {
+        // 01:30 happens twice on November 7th, and the job only runs the first time
+        let schedule = Schedule::cron("30 1 * * *").unwrap();
+        let next = schedule.next_after_with(utc(2021, 11, 6, 12, 0), &eastern);
+        assert_eq!(next, Some(utc(2021, 11, 7, 5, 30)));
+
+        let next = schedule.next_after_with(next.unwrap(), &eastern);
+        assert_eq!(next, Some(utc(2021, 11, 8, 6, 30)));
+    }
+
+    #[test]
+    fn february_30th() {
+        let schedule = Schedule::cron("0 0 30 2 *").unwrap().utc();
+        assert_eq!(schedule.next_after(0), None);
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // August 1st 2021 was a Sunday: the job runs on the 10th, and on every Monday
+        let schedule = Schedule::cron("0 0 10 * mon").unwrap().utc();
+        let mut time = utc(2021, 8, 1, 0, 0);
+        let mut runs = Vec::new();
+        for _ in 0..4 {
+            time = schedule.next_after(time).unwrap();
+            runs.push(time);
+        }
+        assert_eq!(
+            runs,
+            vec![
+                utc(2021, 8, 2, 0, 0),
+                utc(2021, 8, 9, 0, 0),
+                utc(2021, 8, 10, 0, 0),
+                utc(2021, 8, 16, 0, 0),
+            ]
+        );
+
+        // with only one of them restricted, both must match
+        let schedule = Schedule::cron("0 0 * * mon").unwrap().utc();
+        assert_eq!(
+            schedule.next_after(utc(2021, 8, 9, 0, 0)),
+            Some(utc(2021, 8, 16, 0, 0))
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use crate::{
+    Batch, FsEvent, FsEventBatch, FsEventCB, FsEventHandle, FsPollBatch, FsPollCB, FsPollHandle,
+    HandleTrait, ReadBatch, ReadCB, ReadonlyBuf, Signal, SignalBatch, SignalCB, SignalHandle, Stat,
+    StreamHandle, TimerHandle,
+};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+callbacks! {
+    pub FsEventBatchCB(batch: FsEventBatch);
+    pub FsPollBatchCB(batch: FsPollBatch);
+    pub ReadBatchCB(batch: ReadBatch);
+    pub SignalBatchCB(batch: SignalBatch);
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Debounce,
+    Throttle,
+}
+
+/// How a Batcher coalesces events: as a debounce or a throttle, with a period in milliseconds, and
+/// which edges of a burst are delivered.
+///
+/// A debounce waits for a quiet period: the batch is delivered once no event has arrived for
+/// `wait` milliseconds. A throttle delivers at most one batch every `interval` milliseconds, no
+/// matter how long the burst lasts.
+///
+/// With the leading edge, the first event of a burst is delivered right away, in a batch of its
+/// own. With the trailing edge, the events that arrive after that are collected and delivered
+/// together at the end of the period. Events that arrive during a period while the trailing edge
+/// is disabled are discarded. By default, only the trailing edge is delivered.
+///
+/// A Coalescer is only a description: each call to batcher(), fs_event(), fs_poll(), read() or
+/// signal() creates an independent Batcher with its own TimerHandle.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{FsEventBatch, FsEventFlags};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let mut watcher = r#loop.fs_event().unwrap();
+/// let on_change = r#loop
+///     .debounce(100)
+///     .fs_event(|batch: FsEventBatch| {
+///         for (filename, events) in batch.events() {
+///             println!("{:?}: {:?}", filename, events);
+///         }
+///     })
+///     .unwrap();
+/// watcher.start("src", FsEventFlags::RECURSIVE, on_change).unwrap();
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Coalescer {
+    r#loop: crate::Loop,
+    mode: Mode,
+    period: u64,
+    leading: bool,
+    trailing: bool,
+}
+
+impl Coalescer {
+    /// Deliver a batch once no event has arrived for wait milliseconds.
+    pub fn debounce(r#loop: &crate::Loop, wait: u64) -> Coalescer {
+        Coalescer {
+            r#loop: r#loop.clone(),
+            mode: Mode::Debounce,
+            period: wait,
+            leading: false,
+            trailing: true,
+        }
+    }
+
+    /// Deliver at most one batch every interval milliseconds.
+    pub fn throttle(r#loop: &crate::Loop, interval: u64) -> Coalescer {
+        Coalescer {
+            r#loop: r#loop.clone(),
+            mode: Mode::Throttle,
+            period: interval,
+            leading: false,
+            trailing: true,
+        }
+    }
+
+    /// Whether the first event of a burst is delivered immediately. Default: false
+    pub fn leading(mut self, leading: bool) -> Coalescer {
+        self.leading = leading;
+        self
+    }
+
+    /// Whether the events of a burst are delivered at the end of the period. Default: true
+    pub fn trailing(mut self, trailing: bool) -> Coalescer {
+        self.trailing = trailing;
+        self
+    }
+
+    /// Create a Batcher that delivers batches of type B to the callback. Events are added with
+    /// Batcher::push().
+    pub fn batcher<B: Batch, F: FnMut(B) + 'static>(&self, cb: F) -> crate::Result<Batcher<B>> {
+        let timer = self.r#loop.timer()?;
+        Ok(Batcher {
+            state: Rc::new(RefCell::new(BatcherState {
+                timer,
+                mode: self.mode,
+                period: self.period,
+                leading: self.leading,
+                trailing: self.trailing,
+                active: false,
+                batch: None,
+                cb: Some(Box::new(cb)),
+            })),
+        })
+    }
+
+    /// Wrap a callback for FsEventHandle::start(). The events are merged per file name.
+    pub fn fs_event<CB: Into<FsEventBatchCB<'static>>>(
+        &self,
+        cb: CB,
+    ) -> crate::Result<FsEventCB<'static>> {
+        let mut cb = cb.into();
+        let batcher = self.batcher(move |batch| cb.call(batch))?;
+        Ok(FsEventCB::new(
+            move |handle: FsEventHandle,
+                  filename: Option<Cow<str>>,
+                  events: FsEvent,
+                  status: crate::Result<u32>| {
+                batcher.push((handle, filename.map(Cow::into_owned), events, status))
+            },
+        ))
+    }
+
+    /// Wrap a callback for FsPollHandle::start(). The batch spans from the previous stat of the
+    /// first change to the current stat of the last one.
+    pub fn fs_poll<CB: Into<FsPollBatchCB<'static>>>(
+        &self,
+        cb: CB,
+    ) -> crate::Result<FsPollCB<'static>> {
+        let mut cb = cb.into();
+        let batcher = self.batcher(move |batch| cb.call(batch))?;
+        Ok(FsPollCB::new(
+            move |handle: FsPollHandle, status: crate::Result<u32>, prev: Stat, curr: Stat| {
+                batcher.push((handle, status, prev, curr))
+            },
+        ))
+    }
+
+    /// Wrap a callback for StreamHandle::read_start(). The data is copied out of the read buffers
+    /// and concatenated; the buffers are deallocated, as if by ReadonlyBuf::dealloc().
+    pub fn read<CB: Into<ReadBatchCB<'static>>>(&self, cb: CB) -> crate::Result<ReadCB<'static>> {
+        let mut cb = cb.into();
+        let batcher = self.batcher(move |batch| cb.call(batch))?;
+        Ok(ReadCB::new(
+            move |stream: StreamHandle, nread: crate::Result<usize>, mut buf: ReadonlyBuf| {
+                let data = nread.map(|len| {
+                    if len > 0 {
+                        buf[..len].to_vec()
+                    } else {
+                        Vec::new()
+                    }
+                });
+                buf.dealloc();
+                batcher.push((stream, data))
+            },
+        ))
+    }
+
+    /// Wrap a callback for SignalHandle::start(). The batch counts the arrivals of each signal.
+    pub fn signal<CB: Into<SignalBatchCB<'static>>>(
+        &self,
+        cb: CB,
+    ) -> crate::Result<SignalCB<'static>> {
+        let mut cb = cb.into();
+        let batcher = self.batcher(move |batch| cb.call(batch))?;
+        Ok(SignalCB::new(
+            move |handle: SignalHandle, signal: Signal| batcher.push((handle, signal)),
+        ))
+    }
+}
+
+struct BatcherState<B> {
+    timer: TimerHandle,
+    mode: Mode,
+    period: u64,
+    leading: bool,
+    trailing: bool,
+
+    // true while a burst (debounce) or a period (throttle) is in progress
+    active: bool,
+    batch: Option<B>,
+    cb: Option<Box<dyn FnMut(B)>>,
+}
+
+impl<B> Drop for BatcherState<B> {
+    fn drop(&mut self) {
+        if !self.timer.is_closing() {
+            self.timer.close(());
+        }
+    }
+}
+
+/// Coalesces events into batches, which are delivered to a callback as described by the
+/// Coalescer that created it.
+///
+/// A Batcher owns a TimerHandle, which is closed when the last clone of the Batcher is dropped. A
+/// batch that is pending at that point is discarded. The wrappers that are returned by
+/// Coalescer::fs_event() and friends hold a Batcher, so its timer is closed along with the handle
+/// that the wrapper was given to.
+pub struct Batcher<B: Batch> {
+    state: Rc<RefCell<BatcherState<B>>>,
+}
+
+impl<B: Batch> Clone for Batcher<B> {
+    fn clone(&self) -> Batcher<B> {
+        Batcher {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<B: Batch> Batcher<B> {
+    /// Add an event.
+    pub fn push(&self, event: B::Event) {
+        let leading = {
+            let mut state = self.state.borrow_mut();
+            let starting = !state.active;
+            let mut leading = None;
+            if starting && state.leading {
+                leading = Some(B::new(event));
+            } else if state.trailing {
+                match &mut state.batch {
+                    Some(batch) => batch.add(event),
+                    batch => *batch = Some(B::new(event)),
+                }
+            }
+
+            // a debounce restarts the wait with every event; a throttle's period runs its course
+            if starting || matches!(state.mode, Mode::Debounce) {
+                state.active = true;
+                let period = state.period;
+                arm(&mut state.timer, Rc::downgrade(&self.state), period);
+            }
+            leading
+        };
+
+        if let Some(batch) = leading {
+            deliver(&self.state, batch);
+        }
+    }
+
+    /// Deliver the pending batch now, if there is one, and end the burst.
+    pub fn flush(&self) {
+        let batch = {
+            let mut state = self.state.borrow_mut();
+            state.active = false;
+            let _ = state.timer.stop();
+            state.batch.take()
+        };
+        if let Some(batch) = batch {
+            deliver(&self.state, batch);
+        }
+    }
+
+    /// Discard the pending batch, if there is one, and end the burst.
+    pub fn cancel(&self) {
+        let batch = {
+            let mut state = self.state.borrow_mut();
+            state.active = false;
+            let _ = state.timer.stop();
+            state.batch.take()
+        };
+
+        // the batch may hold resources that should not be dropped while the state is borrowed
+        drop(batch);
+    }
+
+    /// Returns true if there is a batch waiting to be delivered.
+    pub fn is_pending(&self) -> bool {
+        self.state.borrow().batch.is_some()
+    }
+}
+
+/// Start the timer for a period. The timer holds a weak reference, so that it does not keep the
+/// Batcher alive.
+fn arm<B: Batch>(timer: &mut TimerHandle, state: Weak<RefCell<BatcherState<B>>>, period: u64) {
+    let _ = timer.start(period, 0, move |_| {
+        if let Some(state) = state.upgrade() {
+            expire(&state);
+        }
+    });
+}
+
+/// Called when a period ends. A throttle with a pending batch starts another period, so that the
+/// events that arrive in the meantime are held back as well.
+fn expire<B: Batch>(state: &Rc<RefCell<BatcherState<B>>>) {
+    let batch = {
+        let mut s = state.borrow_mut();
+        let batch = s.batch.take();
+        if batch.is_some() && matches!(s.mode, Mode::Throttle) {
+            let period = s.period;
+            arm(&mut s.timer, Rc::downgrade(state), period);
+        } else {
+            s.active = false;
+        }
+        batch
+    };
+    if let Some(batch) = batch {
+        deliver(state, batch);
+    }
+}
+
+/// Call the callback. It is taken out of the state while it runs, so that it can use the Batcher.
+fn deliver<B: Batch>(state: &Rc<RefCell<BatcherState<B>>>, batch: B) {
+    let cb = state.borrow_mut().cb.take();
+    if let Some(mut cb) = cb {
+        cb(batch);
+        let mut s = state.borrow_mut();
+        s.cb.get_or_insert(cb);
+    }
+}
+
+impl crate::Loop {
+    /// Create a Coalescer that delivers a batch once no event has arrived for wait milliseconds.
+    pub fn debounce(&self, wait: u64) -> Coalescer {
+        Coalescer::debounce(self, wait)
+    }
+
+    /// Create a Coalescer that delivers at most one batch every interval milliseconds.
+    pub fn throttle(&self, interval: u64) -> Coalescer {
+        Coalescer::throttle(self, interval)
+    }
+}
@@ -0,0 +1,17 @@
+pub mod wheel;
+pub use wheel::*;
+
+pub mod retry;
+pub use retry::*;
+
+pub mod batch;
+pub use batch::*;
+
+pub mod debounce;
+pub use debounce::*;
+
+pub mod cron;
+pub use cron::*;
+
+pub mod scheduler;
+pub use scheduler::*;
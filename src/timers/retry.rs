@@ -0,0 +1,407 @@
+use crate::{HandleTrait, TimerHandle};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Information about an attempt.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RetryAttempt {
+    /// The attempt number, starting at 1.
+    pub number: u32,
+
+    /// Milliseconds since the retry was started, when the attempt began.
+    pub elapsed: u64,
+
+    /// Milliseconds that were waited before the attempt, after the previous one failed. Zero for
+    /// the first attempt.
+    pub delay: u64,
+}
+
+/// How a retry finished.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetryOutcome {
+    /// An attempt succeeded.
+    Succeeded,
+
+    /// The maximum number of attempts failed. The error from the last attempt is included.
+    AttemptsExhausted(crate::Error),
+
+    /// The next attempt would have started after the deadline. The error from the last attempt is
+    /// included.
+    DeadlineExceeded(crate::Error),
+
+    /// The retry was canceled.
+    Canceled,
+
+    /// The retry could not go on because of an error of its own, such as a failure to start the
+    /// timer for the next attempt.
+    Failed(crate::Error),
+}
+
+callbacks! {
+    pub RetryOperationCB(context: RetryContext);
+    pub RetryDoneCB(outcome: RetryOutcome, last: RetryAttempt);
+    pub RetryFailureCB(attempt: RetryAttempt, error: crate::Error, next_delay: u64);
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Phase {
+    Idle,
+    Running,
+    Done,
+}
+
+struct RetryState {
+    timer: TimerHandle,
+    initial_delay: u64,
+    max_delay: u64,
+    multiplier: f64,
+    max_attempts: u32,
+    deadline: Option<u64>,
+    jitter: bool,
+    phase: Phase,
+    started: u64,
+    attempt: RetryAttempt,
+    operation_cb: RetryOperationCB<'static>,
+    failure_cb: RetryFailureCB<'static>,
+    done_cb: RetryDoneCB<'static>,
+}
+
+type State = Rc<RefCell<RetryState>>;
+
+impl RetryState {
+    /// The delay before the next attempt, after `failures` attempts have failed: the exponential
+    /// backoff capped at max_delay, and with full jitter, a random delay between zero and that.
+    fn delay(&self, failures: u32) -> u64 {
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_delay as f64 * self.multiplier.powi(exponent);
+        let backoff = if backoff.is_finite() {
+            (backoff as u64).min(self.max_delay)
+        } else {
+            self.max_delay
+        };
+        if !self.jitter || backoff == 0 {
+            return backoff;
+        }
+
+        match crate::Loop::random_sync(8, 0) {
+            Ok(bytes) => {
+                let mut random = [0u8; 8];
+                random.copy_from_slice(&bytes);
+                u64::from_ne_bytes(random) % backoff.saturating_add(1)
+            }
+            Err(_) => backoff,
+        }
+    }
+}
+
+/// Run the next attempt.
+fn run_attempt(state: &State, delay: u64) {
+    let (mut cb, context) = {
+        let mut s = state.borrow_mut();
+        if s.phase != Phase::Running {
+            return;
+        }
+        let now = s.timer.get_loop().now();
+        s.attempt = RetryAttempt {
+            number: s.attempt.number + 1,
+            elapsed: now.saturating_sub(s.started),
+            delay,
+        };
+        let context = RetryContext {
+            state: state.clone(),
+            attempt: s.attempt,
+            reported: Cell::new(false),
+        };
+        (std::mem::take(&mut s.operation_cb), context)
+    };
+
+    cb.call(context);
+
+    let mut s = state.borrow_mut();
+    if s.phase != Phase::Done {
+        s.operation_cb.restore(cb);
+    }
+}
+
+/// Finish the retry and report the outcome.
+fn finish(state: &State, outcome: RetryOutcome) {
+    let (mut timer, mut cb, last, callbacks) = {
+        let mut s = state.borrow_mut();
+        if s.phase == Phase::Done {
+            return;
+        }
+        s.phase = Phase::Done;
+        let callbacks = (
+            std::mem::take(&mut s.operation_cb),
+            std::mem::take(&mut s.failure_cb),
+        );
+        (
+            s.timer,
+            std::mem::take(&mut s.done_cb),
+            s.attempt,
+            callbacks,
+        )
+    };
+
+    // the callbacks may hold a clone of the retry, so they are dropped once the state is no
+    // longer borrowed
+    drop(callbacks);
+    if !timer.is_closing() {
+        timer.close(());
+    }
+    cb.call(outcome, last);
+}
+
+/// An attempt failed: give up, or schedule the next attempt.
+fn attempt_failed(state: &State, attempt: RetryAttempt, error: crate::Error) {
+    let (mut timer, delay) = {
+        let s = state.borrow();
+        if s.phase != Phase::Running || s.attempt.number != attempt.number {
+            return;
+        }
+        if s.max_attempts > 0 && attempt.number >= s.max_attempts {
+            drop(s);
+            return finish(state, RetryOutcome::AttemptsExhausted(error));
+        }
+
+        let delay = s.delay(attempt.number);
+        if let Some(deadline) = s.deadline {
+            let elapsed = s.timer.get_loop().now().saturating_sub(s.started);
+            if elapsed.saturating_add(delay) > deadline {
+                drop(s);
+                return finish(state, RetryOutcome::DeadlineExceeded(error));
+            }
+        }
+        (s.timer, delay)
+    };
+
+    let mut failure_cb = std::mem::take(&mut state.borrow_mut().failure_cb);
+    failure_cb.call(attempt, error, delay);
+    {
+        let mut s = state.borrow_mut();
+        if s.phase != Phase::Done {
+            s.failure_cb.restore(failure_cb);
+        }
+        if s.phase != Phase::Running {
+            return;
+        }
+    }
+
+    let timer_state = state.clone();
+    if let Err(e) = timer.start(delay, 0, move |_| run_attempt(&timer_state, delay)) {
+        finish(state, RetryOutcome::Failed(e));
+    }
+}
+
+/// Passed to the operation for each attempt. The operation must call succeeded() or failed() once
+/// the attempt is complete. If the context is dropped without either, the attempt fails with
+/// ECANCELED.
+pub struct RetryContext {
+    state: State,
+    attempt: RetryAttempt,
+    reported: Cell<bool>,
+}
+
+impl RetryContext {
+    /// Information about this attempt.
+    pub fn attempt(&self) -> RetryAttempt {
+        self.attempt
+    }
+
+    /// Report that the attempt succeeded. Only the first report for an attempt has any effect.
+    pub fn succeeded(&self) {
+        if self.reported.replace(true) {
+            return;
+        }
+        let current = {
+            let s = self.state.borrow();
+            s.phase == Phase::Running && s.attempt.number == self.attempt.number
+        };
+        if current {
+            finish(&self.state, RetryOutcome::Succeeded);
+        }
+    }
+
+    /// Report that the attempt failed. Only the first report for an attempt has any effect.
+    pub fn failed(&self, error: crate::Error) {
+        if self.reported.replace(true) {
+            return;
+        }
+        attempt_failed(&self.state, self.attempt, error);
+    }
+}
+
+impl Drop for RetryContext {
+    fn drop(&mut self) {
+        self.failed(crate::Error::ECANCELED);
+    }
+}
+
+/// Re-invokes an asynchronous operation with exponential backoff until it succeeds.
+///
+/// The operation is a callback that is called with a RetryContext for each attempt. It starts
+/// some asynchronous work - connecting a socket, for example - and reports the result through the
+/// context. After a failed attempt, the next one is delayed by initial_delay * multiplier^n
+/// milliseconds (n being the number of previous failures), capped at max_delay. By default, "full
+/// jitter" is applied: the actual delay is a random number of milliseconds between zero and that,
+/// drawn with Loop::random_sync(), which spreads out the retries of many clients that failed at
+/// the same time.
+///
+/// The retry gives up once max_attempts attempts have failed, or if the next attempt would start
+/// after the deadline. An attempt that is in progress when the deadline passes is allowed to
+/// finish. Either way, and on success, the done callback is called with the outcome and the last
+/// attempt. A retry uses a TimerHandle, which is closed when it finishes, so it can only be started
+/// once.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{RetryAttempt, RetryContext, RetryOutcome};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let retry = r#loop
+///     .retry()
+///     .unwrap()
+///     .max_attempts(10)
+///     .max_delay(5000)
+///     .on_done(|outcome: RetryOutcome, last: RetryAttempt| {
+///         println!("{:?} after {} attempts", outcome, last.number)
+///     });
+/// let addr = "127.0.0.1:8080".parse().unwrap();
+/// let connect_loop = r#loop.clone();
+/// retry
+///     .start(move |context: RetryContext| {
+///         let mut tcp = connect_loop.tcp().unwrap();
+///         let result = tcp.connect(&addr, move |req: libuv::ConnectReq, status| match status {
+///             Ok(_) => context.succeeded(),
+///             Err(e) => {
+///                 req.handle().close(());
+///                 context.failed(e);
+///             }
+///         });
+///         if result.is_err() {
+///             // dropping the context fails the attempt
+///             tcp.close(());
+///         }
+///     })
+///     .unwrap();
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Retry {
+    state: State,
+}
+
+impl Retry {
+    /// Create a retry with an initial delay of 100ms, a multiplier of 2, a maximum delay of 30
+    /// seconds, at most 5 attempts, no deadline, and full jitter.
+    pub fn new(r#loop: &crate::Loop) -> crate::Result<Retry> {
+        let timer = r#loop.timer()?;
+        Ok(Retry {
+            state: Rc::new(RefCell::new(RetryState {
+                timer,
+                initial_delay: 100,
+                max_delay: 30_000,
+                multiplier: 2.0,
+                max_attempts: 5,
+                deadline: None,
+                jitter: true,
+                phase: Phase::Idle,
+                started: 0,
+                attempt: Default::default(),
+                operation_cb: ().into(),
+                failure_cb: ().into(),
+                done_cb: ().into(),
+            })),
+        })
+    }
+
+    /// Set the delay after the first failure, in milliseconds.
+    pub fn initial_delay(self, delay: u64) -> Retry {
+        self.state.borrow_mut().initial_delay = delay;
+        self
+    }
+
+    /// Set the maximum delay between attempts, in milliseconds.
+    pub fn max_delay(self, delay: u64) -> Retry {
+        self.state.borrow_mut().max_delay = delay;
+        self
+    }
+
+    /// Set the factor that the delay grows by after each failure.
+    pub fn multiplier(self, multiplier: f64) -> Retry {
+        self.state.borrow_mut().multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Set the maximum number of attempts. Zero means there is no limit.
+    pub fn max_attempts(self, attempts: u32) -> Retry {
+        self.state.borrow_mut().max_attempts = attempts;
+        self
+    }
+
+    /// Give up if the next attempt would start more than deadline milliseconds after the retry was
+    /// started.
+    pub fn deadline(self, deadline: u64) -> Retry {
+        self.state.borrow_mut().deadline = Some(deadline);
+        self
+    }
+
+    /// Turn full jitter on or off. Without jitter, the delays are exactly the exponential backoff.
+    pub fn jitter(self, jitter: bool) -> Retry {
+        self.state.borrow_mut().jitter = jitter;
+        self
+    }
+
+    /// Set a callback that is called after each failed attempt that will be retried, with the
+    /// attempt, its error, and the delay until the next attempt.
+    pub fn on_failure<CB: Into<RetryFailureCB<'static>>>(self, cb: CB) -> Retry {
+        self.state.borrow_mut().failure_cb = cb.into();
+        self
+    }
+
+    /// Set the callback that reports how the retry finished.
+    pub fn on_done<CB: Into<RetryDoneCB<'static>>>(self, cb: CB) -> Retry {
+        self.state.borrow_mut().done_cb = cb.into();
+        self
+    }
+
+    /// Start the retry. The first attempt is made right away. Returns EALREADY if the retry was
+    /// already started.
+    pub fn start<CB: Into<RetryOperationCB<'static>>>(&self, cb: CB) -> crate::Result<()> {
+        {
+            let mut s = self.state.borrow_mut();
+            if s.phase != Phase::Idle {
+                return Err(crate::Error::EALREADY);
+            }
+            s.phase = Phase::Running;
+            s.started = s.timer.get_loop().now();
+            s.operation_cb = cb.into();
+        }
+        run_attempt(&self.state, 0);
+        Ok(())
+    }
+
+    /// Cancel the retry. No more attempts are made, and the done callback is called with
+    /// RetryOutcome::Canceled. An attempt that is in progress is not interrupted, but its result
+    /// is ignored.
+    pub fn cancel(&self) {
+        finish(&self.state, RetryOutcome::Canceled);
+    }
+
+    /// Returns true if the retry has been started and has not finished.
+    pub fn is_running(&self) -> bool {
+        self.state.borrow().phase == Phase::Running
+    }
+
+    /// The latest attempt.
+    pub fn attempt(&self) -> RetryAttempt {
+        self.state.borrow().attempt
+    }
+}
+
+impl crate::Loop {
+    /// Create a Retry with the default settings.
+    pub fn retry(&self) -> crate::Result<Retry> {
+        Retry::new(self)
+    }
+}
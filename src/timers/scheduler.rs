@@ -0,0 +1,328 @@
+use crate::{HandleTrait, Schedule, TimerHandle};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A difference between the real-time clock and hrtime() that is larger than this, in
+/// milliseconds, is treated as a change of the system clock.
+const JUMP_TOLERANCE: i64 = 1000;
+
+/// Identifies a job that was added to a Scheduler.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct JobId(u64);
+
+/// Information about a run of a job.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JobRun {
+    /// The job that is running.
+    pub job: JobId,
+
+    /// When the job was scheduled to run, in milliseconds since the UNIX epoch.
+    pub scheduled: i64,
+
+    /// When the job actually ran, in milliseconds since the UNIX epoch.
+    pub time: i64,
+}
+
+callbacks! {
+    pub ScheduledJobCB(scheduler: Scheduler, run: JobRun);
+    pub ClockJumpCB(scheduler: Scheduler, jump: i64);
+}
+
+struct Job {
+    id: JobId,
+    schedule: Schedule,
+    next: Option<i64>,
+    cb: ScheduledJobCB<'static>,
+}
+
+struct SchedulerState {
+    timer: TimerHandle,
+    jobs: Vec<Job>,
+    next_id: u64,
+    check_interval: u64,
+    clock_jump_cb: ClockJumpCB<'static>,
+
+    // the real-time clock and hrtime(), in milliseconds, when the timer was last started
+    armed_at: Option<(i64, u64)>,
+    closed: bool,
+}
+
+type State = Rc<RefCell<SchedulerState>>;
+
+/// The current time from the real-time clock, in milliseconds since the UNIX epoch.
+fn realtime() -> crate::Result<i64> {
+    let now = crate::clock_gettime(crate::ClockId::Realtime)?;
+    Ok(now.sec * 1000 + now.nsec as i64 / 1_000_000)
+}
+
+/// Start the timer for the next job. The timer fires at least every check_interval milliseconds,
+/// so that changes of the system clock are noticed.
+fn arm(state: &State) {
+    let (mut timer, timeout) = {
+        let mut s = state.borrow_mut();
+        if s.closed {
+            return;
+        }
+        let next = match s.jobs.iter().filter_map(|job| job.next).min() {
+            Some(next) => next,
+            None => {
+                s.armed_at = None;
+                let _ = s.timer.stop();
+                return;
+            }
+        };
+        let timeout = match realtime() {
+            Ok(now) => {
+                s.armed_at = Some((now, crate::hrtime() / 1_000_000));
+                ((next - now).max(0) as u64).min(s.check_interval)
+            }
+
+            // try again later
+            Err(_) => {
+                s.armed_at = None;
+                s.check_interval
+            }
+        };
+        (s.timer, timeout)
+    };
+
+    let timer_state = state.clone();
+    let _ = timer.start(timeout, 0, move |_| on_timer(&timer_state));
+}
+
+/// Called when the timer fires: if the system clock changed, the jobs are rescheduled from the
+/// current time. Then the jobs that are due are run, and the timer is started for the next one.
+fn on_timer(state: &State) {
+    let now = match realtime() {
+        Ok(now) => now,
+        Err(_) => return arm(state),
+    };
+    let hrnow = crate::hrtime() / 1_000_000;
+
+    let jump = {
+        let mut s = state.borrow_mut();
+        let jump = s.armed_at.take().and_then(|(real, hr)| {
+            let jump = (now - real) - (hrnow - hr) as i64;
+            if jump.abs() > JUMP_TOLERANCE {
+                Some(jump)
+            } else {
+                None
+            }
+        });
+        if jump.is_some() {
+            for job in s.jobs.iter_mut() {
+                job.next = job.schedule.next_after(now - 1);
+            }
+        }
+        jump
+    };
+
+    let scheduler = Scheduler {
+        state: state.clone(),
+    };
+    if let Some(jump) = jump {
+        let mut cb = std::mem::take(&mut state.borrow_mut().clock_jump_cb);
+        cb.call(scheduler.clone(), jump);
+        let mut s = state.borrow_mut();
+        s.clock_jump_cb.restore(cb);
+    }
+
+    let mut due: Vec<(i64, JobId)> = state
+        .borrow()
+        .jobs
+        .iter()
+        .filter_map(|job| match job.next {
+            Some(next) if next <= now => Some((next, job.id)),
+            _ => None,
+        })
+        .collect();
+    due.sort_by_key(|(next, id)| (*next, id.0));
+
+    for (scheduled, id) in due {
+        // the callback is taken out of the job while it runs, so that it can use the scheduler;
+        // a job that an earlier callback removed is skipped
+        let mut cb = {
+            let mut s = state.borrow_mut();
+            if s.closed {
+                return;
+            }
+            match s.jobs.iter_mut().find(|job| job.id == id) {
+                Some(job) => {
+                    job.next = job.schedule.next_after(now);
+                    std::mem::take(&mut job.cb)
+                }
+                None => continue,
+            }
+        };
+        cb.call(
+            scheduler.clone(),
+            JobRun {
+                job: id,
+                scheduled,
+                time: now,
+            },
+        );
+        let mut s = state.borrow_mut();
+        if let Some(job) = s.jobs.iter_mut().find(|job| job.id == id) {
+            job.cb.restore(cb);
+        }
+    }
+
+    arm(state);
+}
+
+/// Runs jobs on wall-clock schedules, such as cron expressions or "every day at 02:00 local".
+///
+/// Fire times are computed from the real-time clock, clock_gettime(ClockId::Realtime), and a
+/// single TimerHandle is started for the job that runs next. Because the real-time clock can be
+/// changed - by an administrator, or by NTP - the timer wakes up at least every check_interval
+/// milliseconds (a minute, by default) and compares the time that passed on the real-time clock
+/// against hrtime(). If they differ by more than a second, the system clock jumped: every job is
+/// rescheduled from the current time and the clock jump callback is called. Runs that a forward
+/// jump skipped over are not made up. Note that on some platforms hrtime() does not advance while
+/// the system is suspended, so resuming from suspend also counts as a jump.
+///
+/// If the loop is busy and a job's time passes, the job runs once as soon as possible.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{JobRun, Scheduler};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let scheduler = r#loop.scheduler().unwrap();
+/// scheduler
+///     .add(
+///         "every day at 02:00 local".parse().unwrap(),
+///         |_: Scheduler, run: JobRun| println!("vacuuming, {}ms late", run.time - run.scheduled),
+///     )
+///     .unwrap();
+/// r#loop.run(RunMode::Default).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Scheduler {
+    state: State,
+}
+
+impl Scheduler {
+    /// Create a scheduler without any jobs.
+    pub fn new(r#loop: &crate::Loop) -> crate::Result<Scheduler> {
+        let timer = r#loop.timer()?;
+        Ok(Scheduler {
+            state: Rc::new(RefCell::new(SchedulerState {
+                timer,
+                jobs: Vec::new(),
+                next_id: 0,
+                check_interval: 60_000,
+                clock_jump_cb: ().into(),
+                armed_at: None,
+                closed: false,
+            })),
+        })
+    }
+
+    /// The longest time, in milliseconds, between checks for changes of the system clock.
+    /// Default: 60000
+    pub fn check_interval(self, check_interval: u64) -> Scheduler {
+        self.state.borrow_mut().check_interval = check_interval.max(1);
+        arm(&self.state);
+        self
+    }
+
+    /// Called when a change of the system clock is detected, with the size of the jump in
+    /// milliseconds: positive if the clock jumped forward.
+    pub fn on_clock_jump<CB: Into<ClockJumpCB<'static>>>(self, cb: CB) -> Scheduler {
+        self.state.borrow_mut().clock_jump_cb = cb.into();
+        self
+    }
+
+    /// Add a job that runs the callback on the schedule. Returns EINVAL if the schedule never
+    /// runs.
+    pub fn add<CB: Into<ScheduledJobCB<'static>>>(
+        &self,
+        schedule: Schedule,
+        cb: CB,
+    ) -> crate::Result<JobId> {
+        let next = schedule
+            .next_after(realtime()?)
+            .ok_or(crate::Error::EINVAL)?;
+        let id = {
+            let mut s = self.state.borrow_mut();
+            if s.closed {
+                return Err(crate::Error::EINVAL);
+            }
+            let id = JobId(s.next_id);
+            s.next_id += 1;
+            s.jobs.push(Job {
+                id,
+                schedule,
+                next: Some(next),
+                cb: cb.into(),
+            });
+            id
+        };
+        arm(&self.state);
+        Ok(id)
+    }
+
+    /// Remove a job. Returns false if there is no such job.
+    pub fn remove(&self, id: JobId) -> bool {
+        let job = {
+            let mut s = self.state.borrow_mut();
+            match s.jobs.iter().position(|job| job.id == id) {
+                Some(i) => s.jobs.remove(i),
+                None => return false,
+            }
+        };
+
+        // the callback is dropped once the state is no longer borrowed, in case it holds a clone
+        // of the scheduler
+        drop(job);
+        arm(&self.state);
+        true
+    }
+
+    /// When the job runs next, in milliseconds since the UNIX epoch.
+    pub fn next_run(&self, id: JobId) -> Option<i64> {
+        let s = self.state.borrow();
+        s.jobs
+            .iter()
+            .find(|job| job.id == id)
+            .and_then(|job| job.next)
+    }
+
+    /// The number of jobs.
+    pub fn len(&self) -> usize {
+        self.state.borrow().jobs.len()
+    }
+
+    /// Returns true if there are no jobs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every job and close the scheduler's TimerHandle. The scheduler cannot be used
+    /// afterwards.
+    pub fn close(&self) {
+        let (mut timer, jobs, cb) = {
+            let mut s = self.state.borrow_mut();
+            s.closed = true;
+            s.armed_at = None;
+            (
+                s.timer,
+                std::mem::take(&mut s.jobs),
+                std::mem::take(&mut s.clock_jump_cb),
+            )
+        };
+        drop((jobs, cb));
+        if !timer.is_closing() {
+            timer.close(());
+        }
+    }
+}
+
+impl crate::Loop {
+    /// Create a Scheduler without any jobs.
+    pub fn scheduler(&self) -> crate::Result<Scheduler> {
+        Scheduler::new(self)
+    }
+}
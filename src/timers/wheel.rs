@@ -0,0 +1,540 @@
+use crate::{HandleTrait, TimerHandle};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+callbacks! {
+    pub WheelTimerCB(wheel: TimerWheel, id: WheelTimerId);
+}
+
+/// Each level of the wheel has 64 slots, so a level covers 64 times the range of the one below.
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+
+/// With 6 levels, the wheel covers 64^6 ticks - more than two years with a 1ms tick. Timeouts
+/// beyond that are parked in the last level and cascaded down when they come within range.
+const LEVELS: usize = 6;
+const MAX_RANGE: u64 = 1 << (SLOT_BITS as usize * LEVELS);
+
+/// Identifies a timer in a TimerWheel. Ids are not reused: once a timer fires or is canceled, its
+/// id no longer refers to anything.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WheelTimerId {
+    index: u32,
+    generation: u32,
+}
+
+struct Entry {
+    /// The tick at which the timer fires.
+    deadline: u64,
+    level: usize,
+    slot: usize,
+    prev: Option<u32>,
+    next: Option<u32>,
+    cb: WheelTimerCB<'static>,
+}
+
+/// A slot in the entry slab. The generation is bumped each time the slot is freed, so that stale
+/// ids can be detected.
+struct SlabSlot {
+    generation: u32,
+    entry: Option<Entry>,
+}
+
+struct WheelState {
+    timer: TimerHandle,
+    tick: u64,
+    start: u64,
+
+    /// The last tick that has been processed.
+    current: u64,
+
+    /// The tick that the timer handle is armed for, if any.
+    armed: Option<u64>,
+
+    heads: [[Option<u32>; SLOTS]; LEVELS],
+    occupied: [u64; LEVELS],
+    entries: Vec<SlabSlot>,
+    free: Vec<u32>,
+    len: usize,
+    closed: bool,
+}
+
+type State = Rc<RefCell<WheelState>>;
+
+impl WheelState {
+    /// The tick that a timeout, in milliseconds from now, expires at. Timers never fire early, so
+    /// this is rounded up to the next tick.
+    fn deadline(&self, timeout: u64) -> u64 {
+        let now = self.timer.get_loop().now().saturating_sub(self.start);
+        // u64::MAX is a reasonable "never"
+        let deadline = match now.saturating_add(timeout) {
+            0 => 0,
+            end => (end - 1) / self.tick + 1,
+        };
+        deadline.max(self.current)
+    }
+
+    fn entry(&mut self, id: WheelTimerId) -> Option<&mut Entry> {
+        match self.entries.get_mut(id.index as usize) {
+            Some(slot) if slot.generation == id.generation => slot.entry.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Link an entry into the slot for its deadline.
+    fn link(&mut self, index: u32) {
+        let current = self.current;
+        let head;
+        {
+            let entry = self.entries[index as usize].entry.as_mut().unwrap();
+            let when = entry.deadline.min(current + MAX_RANGE - 1);
+            let significant = 63 - ((when ^ current) | SLOT_MASK).leading_zeros();
+            let level = ((significant / SLOT_BITS) as usize).min(LEVELS - 1);
+            let slot = ((when >> (level as u32 * SLOT_BITS)) & SLOT_MASK) as usize;
+            entry.level = level;
+            entry.slot = slot;
+            entry.prev = None;
+            entry.next = self.heads[level][slot];
+            head = (level, slot);
+        }
+
+        let (level, slot) = head;
+        if let Some(next) = self.heads[level][slot] {
+            self.entries[next as usize].entry.as_mut().unwrap().prev = Some(index);
+        }
+        self.heads[level][slot] = Some(index);
+        self.occupied[level] |= 1 << slot;
+    }
+
+    /// Remove an entry from its slot.
+    fn unlink(&mut self, index: u32) {
+        let (level, slot, prev, next) = {
+            let entry = self.entries[index as usize].entry.as_ref().unwrap();
+            (entry.level, entry.slot, entry.prev, entry.next)
+        };
+        match prev {
+            Some(prev) => self.entries[prev as usize].entry.as_mut().unwrap().next = next,
+            None => self.heads[level][slot] = next,
+        }
+        if let Some(next) = next {
+            self.entries[next as usize].entry.as_mut().unwrap().prev = prev;
+        }
+        if self.heads[level][slot].is_none() {
+            self.occupied[level] &= !(1 << slot);
+        }
+    }
+
+    /// Free an entry's slab slot, returning the entry.
+    fn release(&mut self, index: u32) -> Entry {
+        let slot = &mut self.entries[index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+        self.len -= 1;
+        slot.entry.take().unwrap()
+    }
+
+    /// The level and slot that expire next, and the tick at which they do.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        let mut next: Option<(usize, usize, u64)> = None;
+        for level in 0..LEVELS {
+            let occupied = self.occupied[level];
+            if occupied == 0 {
+                continue;
+            }
+
+            let shift = level as u32 * SLOT_BITS;
+            let slot_range = 1u64 << shift;
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = ((self.current >> shift) & SLOT_MASK) as u32;
+            let slot =
+                (occupied.rotate_right(now_slot).trailing_zeros() + now_slot) as u64 & SLOT_MASK;
+            let level_start = self.current & !(level_range - 1);
+            let mut tick = level_start + slot * slot_range;
+            if tick < self.current {
+                // the slot is in the next rotation of this level
+                tick += level_range;
+            }
+
+            match next {
+                Some((_, _, next_tick)) if next_tick <= tick => {}
+                _ => next = Some((level, slot as usize, tick)),
+            }
+        }
+        next
+    }
+
+    /// Process every slot that expires at or before target. Returns the ids and callbacks of the
+    /// timers that fired.
+    fn advance(&mut self, target: u64) -> Vec<(WheelTimerId, WheelTimerCB<'static>)> {
+        let mut fired = Vec::new();
+        while let Some((level, slot, tick)) = self.next_expiration() {
+            if tick > target {
+                break;
+            }
+            self.current = tick;
+
+            // take the whole list out of the slot, then fire or cascade each entry
+            let mut next = self.heads[level][slot].take();
+            self.occupied[level] &= !(1 << slot);
+            while let Some(index) = next {
+                let (deadline, following) = {
+                    let entry = self.entries[index as usize].entry.as_ref().unwrap();
+                    (entry.deadline, entry.next)
+                };
+                next = following;
+                if deadline <= self.current {
+                    let generation = self.entries[index as usize].generation;
+                    let entry = self.release(index);
+                    fired.push((WheelTimerId { index, generation }, entry.cb));
+                } else {
+                    self.link(index);
+                }
+            }
+        }
+        self.current = self.current.max(target);
+        fired
+    }
+}
+
+/// Arm the timer handle for the next expiration, unless it is already armed for an earlier one.
+/// If force is true, the timer is re-armed even if it is armed for an earlier tick (used after it
+/// fires).
+fn arm(state: &State, force: bool) {
+    let (mut timer, timeout, tick) = {
+        let mut s = state.borrow_mut();
+        if s.closed {
+            return;
+        }
+        let tick = match s.next_expiration() {
+            Some((_, _, tick)) => tick,
+            None => {
+                s.armed = None;
+                drop(s);
+                let mut timer = state.borrow().timer;
+                let _ = timer.stop();
+                return;
+            }
+        };
+        match s.armed {
+            Some(armed) if !force && armed <= tick => return,
+            _ => {}
+        }
+        s.armed = Some(tick);
+        let at = s.start.saturating_add(tick.saturating_mul(s.tick));
+        (s.timer, at.saturating_sub(s.timer.get_loop().now()), tick)
+    };
+
+    let timer_state = state.clone();
+    let result = timer.start(timeout, 0, move |_| on_timer(&timer_state));
+    if result.is_err() {
+        let mut s = state.borrow_mut();
+        if s.armed == Some(tick) {
+            s.armed = None;
+        }
+    }
+}
+
+/// Called when the timer handle fires: advance the wheel to the current time, and dispatch the
+/// timers that have expired.
+fn on_timer(state: &State) {
+    let fired = {
+        let mut s = state.borrow_mut();
+        if s.closed {
+            return;
+        }
+        s.armed = None;
+        let now = s.timer.get_loop().now().saturating_sub(s.start);
+        let target = now / s.tick;
+        s.advance(target)
+    };
+
+    let wheel = TimerWheel {
+        state: state.clone(),
+    };
+    let mut fired = fired.into_iter();
+    for (id, mut cb) in fired.by_ref() {
+        cb.call(wheel.clone(), id);
+
+        // a callback may close the wheel: the timers that fired along with it are canceled too
+        if state.borrow().closed {
+            break;
+        }
+    }
+    drop(fired);
+    arm(state, true);
+}
+
+/// Multiplexes many coarse timeouts onto a single TimerHandle.
+///
+/// Every TimerHandle is a separate allocation and a separate entry in libuv's timer heap. That is
+/// fine for a few timers, but not for a server that keeps an idle timeout for each of hundreds of
+/// thousands of connections. A TimerWheel keeps its timers in a hierarchical timing wheel instead:
+/// inserting, canceling and resetting a timer are O(1), and only one TimerHandle is used, armed
+/// for the next expiration.
+///
+/// Time is divided into ticks of a configurable number of milliseconds, and timers fire on the
+/// first tick at or after their timeout - never early, but up to one tick late. Callbacks are
+/// called on the loop, like any other timer callback. Timers are one-shot; use reset() to start
+/// one again.
+///
+/// While timers are pending, the wheel's TimerHandle keeps the loop alive. close() cancels every
+/// timer and closes the handle.
+///
+/// ```no_run
+/// use libuv::prelude::*;
+/// use libuv::{TimerWheel, WheelTimerId};
+///
+/// let mut r#loop = Loop::default().unwrap();
+/// let wheel = r#loop.timer_wheel(100).unwrap();
+/// let id = wheel
+///     .insert(30_000, |_: TimerWheel, _: WheelTimerId| println!("idle"))
+///     .unwrap();
+///
+/// // there was some activity: push the timeout back
+/// wheel.reset(id, 30_000);
+/// ```
+#[derive(Clone)]
+pub struct TimerWheel {
+    state: State,
+}
+
+impl TimerWheel {
+    /// Create a timer wheel with the given tick, in milliseconds.
+    pub fn new(r#loop: &crate::Loop, tick: u64) -> crate::Result<TimerWheel> {
+        if tick == 0 {
+            return Err(crate::Error::EINVAL);
+        }
+
+        let timer = r#loop.timer()?;
+        Ok(TimerWheel {
+            state: Rc::new(RefCell::new(WheelState {
+                timer,
+                tick,
+                start: r#loop.now(),
+                current: 0,
+                armed: None,
+                heads: [[None; SLOTS]; LEVELS],
+                occupied: [0; LEVELS],
+                entries: Vec::new(),
+                free: Vec::new(),
+                len: 0,
+                closed: false,
+            })),
+        })
+    }
+
+    /// The length of a tick, in milliseconds.
+    pub fn tick(&self) -> u64 {
+        self.state.borrow().tick
+    }
+
+    /// The number of pending timers.
+    pub fn len(&self) -> usize {
+        self.state.borrow().len
+    }
+
+    /// Returns true if there are no pending timers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Start a timer that calls the callback after timeout milliseconds. Returns EINVAL if the
+    /// wheel has been closed.
+    pub fn insert<CB: Into<WheelTimerCB<'static>>>(
+        &self,
+        timeout: u64,
+        cb: CB,
+    ) -> crate::Result<WheelTimerId> {
+        let id = {
+            let mut s = self.state.borrow_mut();
+            if s.closed {
+                return Err(crate::Error::EINVAL);
+            }
+            let deadline = s.deadline(timeout);
+            let entry = Entry {
+                deadline,
+                level: 0,
+                slot: 0,
+                prev: None,
+                next: None,
+                cb: cb.into(),
+            };
+            let index = match s.free.pop() {
+                Some(index) => {
+                    s.entries[index as usize].entry = Some(entry);
+                    index
+                }
+                None => {
+                    s.entries.push(SlabSlot {
+                        generation: 0,
+                        entry: Some(entry),
+                    });
+                    (s.entries.len() - 1) as u32
+                }
+            };
+            s.len += 1;
+            s.link(index);
+            WheelTimerId {
+                index,
+                generation: s.entries[index as usize].generation,
+            }
+        };
+        arm(&self.state, false);
+        Ok(id)
+    }
+
+    /// Cancel a timer. Returns false if it has already fired or been canceled.
+    pub fn cancel(&self, id: WheelTimerId) -> bool {
+        let mut s = self.state.borrow_mut();
+        if s.entry(id).is_none() {
+            return false;
+        }
+        s.unlink(id.index);
+        let entry = s.release(id.index);
+
+        // the timer handle is only re-armed if it was armed for the timer that was canceled, or
+        // stopped if the wheel is now empty, so that it doesn't keep the loop alive. Otherwise it
+        // is left alone: if it fires early, on_timer() re-arms it.
+        let rearm = s.len == 0 || s.armed == Some(entry.deadline);
+        drop(s);
+
+        // the callback is dropped once the state is no longer borrowed, in case it holds a clone
+        // of the wheel
+        drop(entry);
+
+        if rearm {
+            arm(&self.state, true);
+        }
+        true
+    }
+
+    /// Restart a pending timer, so that it fires after timeout milliseconds from now instead.
+    /// Returns false if it has already fired or been canceled, or if the wheel has been closed.
+    pub fn reset(&self, id: WheelTimerId, timeout: u64) -> bool {
+        {
+            let mut s = self.state.borrow_mut();
+            if s.closed || s.entry(id).is_none() {
+                return false;
+            }
+            let deadline = s.deadline(timeout);
+            s.unlink(id.index);
+            s.entry(id).unwrap().deadline = deadline;
+            s.link(id.index);
+        }
+        arm(&self.state, false);
+        true
+    }
+
+    /// Returns true if the timer has not yet fired or been canceled.
+    pub fn is_pending(&self, id: WheelTimerId) -> bool {
+        self.state.borrow_mut().entry(id).is_some()
+    }
+
+    /// Cancel every timer and close the wheel's TimerHandle. Afterwards, insert() returns EINVAL
+    /// and reset() returns false. Calling close() again does nothing.
+    pub fn close(&self) {
+        let (mut timer, entries) = {
+            let mut s = self.state.borrow_mut();
+            if s.closed {
+                return;
+            }
+            s.closed = true;
+            s.heads = [[None; SLOTS]; LEVELS];
+            s.occupied = [0; LEVELS];
+            s.free.clear();
+            s.len = 0;
+            s.armed = None;
+            (s.timer, std::mem::take(&mut s.entries))
+        };
+        drop(entries);
+        if !timer.is_closing() {
+            timer.close(());
+        }
+    }
+}
+
+impl crate::Loop {
+    /// Create a TimerWheel with the given tick, in milliseconds.
+    pub fn timer_wheel(&self, tick: u64) -> crate::Result<TimerWheel> {
+        TimerWheel::new(self, tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run f with a wheel with a 1ms tick, on a loop that is never run: the tests advance the
+    /// wheel by hand. The wheel is closed afterwards, so that the loop can be dropped.
+    fn with_wheel(f: impl FnOnce(&TimerWheel)) {
+        let mut r#loop = crate::Loop::new().unwrap();
+        let wheel = TimerWheel::new(&r#loop, 1).unwrap();
+        f(&wheel);
+        wheel.close();
+        r#loop.run(crate::RunMode::Default).unwrap();
+    }
+
+    fn insert(wheel: &TimerWheel, timeout: u64) -> WheelTimerId {
+        wheel
+            .insert(timeout, |_: TimerWheel, _: WheelTimerId| {})
+            .unwrap()
+    }
+
+    /// Advance the wheel one tick at a time up to last, and return the tick that each timer fired
+    /// at.
+    fn fire_ticks(wheel: &TimerWheel, ids: &[WheelTimerId], last: u64) -> Vec<Option<u64>> {
+        let mut ticks = vec![None; ids.len()];
+        for tick in 0..=last {
+            let fired = wheel.state.borrow_mut().advance(tick);
+            for (id, _) in fired {
+                let index = ids.iter().position(|i| *i == id).unwrap();
+                assert_eq!(ticks[index], None);
+                ticks[index] = Some(tick);
+            }
+        }
+        ticks
+    }
+
+    #[test]
+    fn cascade() {
+        with_wheel(|wheel| {
+            // timeouts on either side of the boundaries between levels
+            let timeouts = [
+                1, 63, 64, 65, 100, 4095, 4096, 4097, 4160, 262_143, 262_144, 262_145, 300_000,
+            ];
+            let ids: Vec<WheelTimerId> = timeouts.iter().map(|t| insert(wheel, *t)).collect();
+            let ticks = fire_ticks(wheel, &ids, 300_001);
+            let expected: Vec<Option<u64>> = timeouts.iter().map(|t| Some(*t)).collect();
+            assert_eq!(ticks, expected);
+            assert!(wheel.is_empty());
+        });
+    }
+
+    #[test]
+    fn clamp_beyond_max_range() {
+        with_wheel(|wheel| {
+            insert(wheel, MAX_RANGE + 10);
+            insert(wheel, u64::MAX);
+            {
+                let s = wheel.state.borrow();
+                assert_eq!(s.occupied[LEVELS - 1].count_ones(), 1);
+                let (_, _, tick) = s.next_expiration().unwrap();
+                assert!(tick < MAX_RANGE);
+            }
+
+            // the timers are cascaded down when they come within range, but don't fire early
+            assert!(wheel.state.borrow_mut().advance(MAX_RANGE + 9).is_empty());
+            assert_eq!(wheel.len(), 2);
+            assert_eq!(wheel.state.borrow_mut().advance(MAX_RANGE + 10).len(), 1);
+
+            // "never" stays parked in the last level
+            assert!(wheel.state.borrow_mut().advance(3 * MAX_RANGE).is_empty());
+            assert_eq!(wheel.len(), 1);
+            let s = wheel.state.borrow();
+            let (level, _, tick) = s.next_expiration().unwrap();
+            assert_eq!(level, LEVELS - 1);
+            assert!(tick < s.current + MAX_RANGE);
+        });
+    }
+}
@@ -0,0 +1,283 @@
+//! Support for the `tracing` feature. Every callback created with the callbacks! macro runs
+//! inside a span named after the callback type (TimerCB, ReadCB, FsCB, etc). The span records the
+//! type of the handle or request that the callback was invoked for, the error status if there is
+//! one, and how long the callback took to run.
+//!
+//! Each call to Loop::run() runs inside a "run" span, and each loop iteration inside an
+//! "iteration" span that is a child of it. Iterations are separated by an unref'd check handle
+//! that is created the first time the loop runs. It does not keep the loop alive, is skipped by
+//! Loop::walk(), and is closed when the loop is closed.
+//!
+//! Callback arguments are inspected using "autoref specialization": the RecordX traits are
+//! implemented on Arg<T> for the argument types we know how to record, and the IgnoreX traits are
+//! implemented on &Arg<T> for everything else. Calling a method on &Arg(&arg) will pick the RecordX
+//! implementation if there is one, and fall back to the IgnoreX implementation otherwise.
+
+use crate::{FromInner, HandleTrait, IntoInner, ReqTrait};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+use tracing::Span;
+use uv::{
+    uv_check_init, uv_check_start, uv_check_t, uv_close, uv_handle_t, uv_run,
+    uv_run_mode_UV_RUN_NOWAIT, uv_unref, uv_walk,
+};
+
+/// Wraps a callback argument so that it can be recorded in a span.
+pub(crate) struct Arg<'a, T>(pub(crate) &'a T);
+
+pub(crate) trait RecordHandle {
+    fn record_handle(&self, span: &Span);
+}
+
+pub(crate) trait IgnoreHandle {
+    fn record_handle(&self, _: &Span) {}
+}
+
+impl<T> IgnoreHandle for &Arg<'_, T> {}
+
+impl<T: crate::ToHandle> RecordHandle for Arg<'_, T> {
+    fn record_handle(&self, span: &Span) {
+        let handle = self.0.to_handle();
+        span.record("handle_type", tracing::field::display(handle.get_type()));
+    }
+}
+
+pub(crate) trait RecordReq {
+    fn record_req(&self, span: &Span);
+}
+
+pub(crate) trait IgnoreReq {
+    fn record_req(&self, _: &Span) {}
+}
+
+impl<T> IgnoreReq for &Arg<'_, T> {}
+
+impl<T: crate::ToReq> RecordReq for Arg<'_, T> {
+    fn record_req(&self, span: &Span) {
+        let req = self.0.to_req();
+        span.record("req_type", tracing::field::display(req.get_type()));
+    }
+}
+
+pub(crate) trait RecordStatus {
+    fn record_status(&self, span: &Span);
+}
+
+pub(crate) trait IgnoreStatus {
+    fn record_status(&self, _: &Span) {}
+}
+
+impl<T> IgnoreStatus for &Arg<'_, T> {}
+
+impl<T> RecordStatus for Arg<'_, crate::Result<T>> {
+    fn record_status(&self, span: &Span) {
+        record_result(span, self.0);
+    }
+}
+
+impl RecordStatus for Arg<'_, crate::FsReq> {
+    fn record_status(&self, span: &Span) {
+        record_result(span, &self.0.result());
+    }
+}
+
+/// Records the error, if any, from a result.
+fn record_result<T>(span: &Span, result: &crate::Result<T>) {
+    if let Err(e) = result {
+        span.record("error", tracing::field::display(e.name()));
+    }
+}
+
+/// Creates the span for a callback. The fields are filled in with the Record traits above.
+pub(crate) fn callback_span(name: &'static str) -> Span {
+    tracing::trace_span!(
+        "callback",
+        callback = name,
+        handle_type = Empty,
+        req_type = Empty,
+        error = Empty,
+        duration_ns = Empty
+    )
+}
+
+/// Keeps a span entered while a callback runs, and records how long it took when dropped.
+pub(crate) struct Timed {
+    span: Option<EnteredSpan>,
+    start: Instant,
+}
+
+impl Timed {
+    pub(crate) fn enter(span: Span) -> Timed {
+        Timed {
+            span: Some(span.entered()),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        if let Some(span) = self.span.take() {
+            span.record("duration_ns", self.start.elapsed().as_nanos() as u64);
+        }
+    }
+}
+
+/// The span for a call to Loop::run(). Records the number of loop iterations that were run.
+pub(crate) struct RunSpan {
+    timed: Timed,
+    r#loop: crate::Loop,
+    loop_count: u64,
+}
+
+impl RunSpan {
+    pub(crate) fn enter(r#loop: &crate::Loop, mode: &crate::RunMode) -> RunSpan {
+        let mode = match mode {
+            crate::RunMode::Default => "default",
+            crate::RunMode::Once => "once",
+            crate::RunMode::NoWait => "nowait",
+        };
+        let span = tracing::debug_span!(
+            "run",
+            mode,
+            iterations = Empty,
+            error = Empty,
+            duration_ns = Empty
+        );
+        RunSpan {
+            timed: Timed::enter(span),
+            r#loop: r#loop.clone(),
+            loop_count: loop_count(r#loop),
+        }
+    }
+
+    pub(crate) fn record_result<T>(&self, result: &crate::Result<T>) {
+        if let Some(span) = self.timed.span.as_ref() {
+            record_result(span, result);
+        }
+    }
+}
+
+impl Drop for RunSpan {
+    fn drop(&mut self) {
+        if let Some(span) = self.timed.span.as_ref() {
+            let iterations = loop_count(&self.r#loop).saturating_sub(self.loop_count);
+            span.record("iterations", iterations);
+        }
+    }
+}
+
+fn loop_count(r#loop: &crate::Loop) -> u64 {
+    r#loop.metrics_info().map(|m| m.loop_count).unwrap_or(0)
+}
+
+/// The spans for the iterations of a loop. An unref'd check handle ends the span of the current
+/// iteration and enters the next one, so that each span is a child of the run span.
+#[derive(Default)]
+pub(crate) struct Iterations {
+    check: Option<*mut uv_check_t>,
+    span: Option<Timed>,
+}
+
+fn iteration_span() -> Span {
+    tracing::trace_span!("iteration", duration_ns = Empty)
+}
+
+/// Callback for the check handle that separates iterations.
+extern "C" fn uv_iteration_check_cb(handle: *mut uv_check_t) {
+    let r#loop = crate::Loop::from_inner(unsafe { (*handle).loop_ });
+    if let Some(iterations) = r#loop.iterations() {
+        let mut iterations = iterations.borrow_mut();
+
+        // the previous span is exited first, so that the next one is a sibling rather than a child
+        iterations.span = None;
+        iterations.span = Some(Timed::enter(iteration_span()));
+    }
+}
+
+/// Callback for uv_close on the check handle.
+extern "C" fn uv_iteration_close_cb(handle: *mut uv_handle_t) {
+    std::mem::drop(unsafe { Box::from_raw(handle as *mut uv_check_t) });
+}
+
+/// Enter the span for the first iteration of a call to Loop::run(), starting the check handle if
+/// this is the first time that the loop runs.
+pub(crate) fn enter_iterations(r#loop: &crate::Loop) {
+    let iterations = match r#loop.iterations() {
+        Some(iterations) => iterations,
+        None => return,
+    };
+    let mut iterations = iterations.borrow_mut();
+    if iterations.check.is_none() {
+        let check = Box::into_raw(Box::new(unsafe { std::mem::zeroed::<uv_check_t>() }));
+        let ret = unsafe { uv_check_init(r#loop.into_inner(), check) };
+        if ret < 0 {
+            std::mem::drop(unsafe { Box::from_raw(check) });
+            return;
+        }
+        unsafe {
+            uv_unref(check as _);
+            uv_check_start(check, Some(uv_iteration_check_cb));
+        }
+        iterations.check = Some(check);
+    }
+    iterations.span = Some(Timed::enter(iteration_span()));
+}
+
+/// Exit the span of the last iteration when Loop::run() returns.
+pub(crate) fn exit_iterations(r#loop: &crate::Loop) {
+    if let Some(iterations) = r#loop.iterations() {
+        let span = iterations.borrow_mut().span.take();
+        std::mem::drop(span);
+    }
+}
+
+/// Returns true if the handle is the check handle that separates iterations. Loop::walk() skips
+/// it, so that it is not visible to users.
+pub(crate) fn is_iteration_check(r#loop: &crate::Loop, handle: *mut uv_handle_t) -> bool {
+    r#loop
+        .iterations()
+        .and_then(|iterations| iterations.borrow().check)
+        .map_or(false, |check| check as *mut uv_handle_t == handle)
+}
+
+/// Callback for uv_walk that counts the handles other than the check handle.
+extern "C" fn uv_count_walk_cb(handle: *mut uv_handle_t, arg: *mut std::os::raw::c_void) {
+    let (check, count) = unsafe { &mut *(arg as *mut (*mut uv_handle_t, usize)) };
+    if handle != *check {
+        *count += 1;
+    }
+}
+
+/// Close the check handle before the loop is closed. uv_loop_close() fails with EBUSY while any
+/// handle is open, so the check handle is closed once it is the last handle, and the loop is run
+/// without blocking to finish closing it.
+pub(crate) fn close_iterations(r#loop: &crate::Loop) {
+    let iterations = match r#loop.iterations() {
+        Some(iterations) => iterations,
+        None => return,
+    };
+    let check = match iterations.borrow().check {
+        Some(check) => check,
+        None => return,
+    };
+
+    let mut others: (*mut uv_handle_t, usize) = (check as _, 0);
+    unsafe {
+        uv_walk(
+            r#loop.into_inner(),
+            Some(uv_count_walk_cb),
+            &mut others as *mut (*mut uv_handle_t, usize) as _,
+        )
+    };
+    if others.1 > 0 {
+        return;
+    }
+
+    iterations.borrow_mut().check = None;
+    unsafe {
+        uv_close(check as _, Some(uv_iteration_close_cb));
+        uv_run(r#loop.into_inner(), uv_run_mode_UV_RUN_NOWAIT);
+    }
+}